@@ -0,0 +1,449 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
+
+use crate::crypto::StoreKey;
+use crate::mcp::{
+    Attachment, AttachmentId, ConversationId, Mention, Message, MessageBody, MessageId, Participant,
+};
+use crate::settings::Settings;
+use crate::store::{shred_file, Store, StoredEvent};
+
+mod database;
+mod frames;
+
+use database::{Database, Row};
+use frames::{BackupReader, Frame};
+
+/// Prefixes Signal Android puts in front of the hex group ID.
+const GROUP_ID_PREFIXES: &[&str] = &["__signal_group__v2__!", "__textsecure_group__!"];
+
+/// Base message types (`type & 0x1f`) for incoming and outgoing messages.
+const BASE_INBOX_TYPE: i64 = 20;
+const OUTGOING_BASE_TYPES: std::ops::RangeInclusive<i64> = 21..=26;
+const BASE_TYPE_MASK: i64 = 0x1f;
+/// Key exchange, group update/leave and timer update rows are not conversation content.
+const NON_CONTENT_BITS: i64 = 0x8000 | 0x10000 | 0x20000 | 0x40000;
+
+/// What an import added to the store.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub messages: usize,
+    pub duplicates: usize,
+    pub expired: usize,
+    pub conversations: usize,
+    pub attachments: usize,
+}
+
+/// Decrypt a Signal Android `.backup` file and append its messages to `store`.
+///
+/// Attachment data is written to the signal-cli attachment directory under
+/// IDs of the form `backup-<account>-<row>`, so exports and retention treat
+/// it like received attachments. Only the owner can read the files, and they
+/// are sealed with the store key when the store is encrypted. Messages already in the store (same author
+/// and timestamp) are skipped, so importing a newer backup later is safe.
+pub async fn import_backup(
+    settings: &Settings,
+    account: &str,
+    store: &Store,
+    backup: &Path,
+    passphrase: &str,
+) -> Result<ImportSummary> {
+    let digits: String = passphrase
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect();
+    ensure!(
+        digits.len() == 30 && digits.bytes().all(|byte| byte.is_ascii_digit()),
+        "the backup passphrase is 30 digits, as shown when backups were enabled"
+    );
+
+    let attachments_dir = settings.attachment_directory();
+    let (parsed, written) = {
+        let backup = backup.to_path_buf();
+        let account = account.to_string();
+        let attachments_dir = attachments_dir.clone();
+        let key = store.key().cloned();
+        tokio::task::spawn_blocking(move || {
+            read_backup(&backup, &digits, &account, &attachments_dir, key.as_deref())
+        })
+        .await
+        .context("backup reader panicked")??
+    };
+
+    let now = Utc::now();
+    let mut summary = ImportSummary::default();
+    let mut messages = parsed.messages(account);
+    messages.sort_by_key(|message| message.timestamp);
+    let mut referenced = HashSet::new();
+    let mut existing: HashMap<ConversationId, HashSet<MessageId>> = HashMap::new();
+    for message in messages {
+        if message
+            .expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            summary.expired += 1;
+            continue;
+        }
+        let known = match existing.get_mut(&message.conversation_id) {
+            Some(known) => known,
+            None => {
+                let known = store
+                    .events(&message.conversation_id)
+                    .await?
+                    .into_iter()
+                    .filter_map(|event| match event {
+                        StoredEvent::Message(message) => Some(message.id),
+                        _ => None,
+                    })
+                    .collect();
+                summary.conversations += 1;
+                existing
+                    .entry(message.conversation_id.clone())
+                    .or_insert(known)
+            }
+        };
+        // A duplicate's attachment file is the one the stored copy already points at.
+        referenced.extend(
+            message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.id.clone()),
+        );
+        if !known.insert(message.id.clone()) {
+            summary.duplicates += 1;
+            continue;
+        }
+        summary.attachments += message.attachments.len();
+        store
+            .append(
+                &message.conversation_id,
+                &StoredEvent::Message(message.clone()),
+            )
+            .await?;
+        summary.messages += 1;
+    }
+
+    // Attachments of skipped rows (calls, expired messages) are not kept.
+    for (id, path) in written {
+        if referenced.contains(&id) {
+            continue;
+        }
+        if let Err(err) = shred_file(&path).await {
+            warn!(?err, path = %path.display(), "failed to remove unused backup attachment");
+        }
+    }
+
+    info!(
+        messages = summary.messages,
+        duplicates = summary.duplicates,
+        expired = summary.expired,
+        attachments = summary.attachments,
+        "backup imported"
+    );
+    Ok(summary)
+}
+
+/// Read every frame, keeping the relevant table rows and writing attachment data to disk.
+fn read_backup(
+    path: &Path,
+    passphrase: &str,
+    account: &str,
+    attachments_dir: &Path,
+    key: Option<&StoreKey>,
+) -> Result<(Database, Vec<(AttachmentId, PathBuf)>)> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut reader = BackupReader::open(BufReader::new(file), passphrase)?;
+    std::fs::create_dir_all(attachments_dir)
+        .with_context(|| format!("failed to create {}", attachments_dir.display()))?;
+
+    let mut database = Database::default();
+    let mut written = Vec::new();
+    let mut frames = 0usize;
+    loop {
+        frames += 1;
+        match reader.next_frame()? {
+            Frame::Statement { sql, parameters } => database.apply(&sql, parameters),
+            Frame::Attachment { row_id, length } => {
+                let id = backup_attachment_id(account, row_id as i64);
+                let target = attachments_dir.join(&id.0);
+                let mut output = BufWriter::new(create_private(&target)?);
+                match key {
+                    Some(key) => {
+                        let mut plaintext = Vec::with_capacity(length as usize);
+                        reader.copy_blob(length, &mut plaintext)?;
+                        output
+                            .write_all(&key.seal_file(&plaintext)?)
+                            .with_context(|| format!("failed to write {}", target.display()))?;
+                    }
+                    None => reader.copy_blob(length, &mut output)?,
+                }
+                output
+                    .flush()
+                    .with_context(|| format!("failed to write {}", target.display()))?;
+                written.push((id, target));
+            }
+            Frame::Blob { length } => reader.skip_blob(length)?,
+            Frame::End => break,
+            Frame::Other => {}
+        }
+    }
+    debug!(frames, attachments = written.len(), "backup frames read");
+    Ok((database, written))
+}
+
+/// Create or truncate `path`, readable and writable by the owner only.
+fn create_private(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options
+        .open(path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    // `mode` only applies to new files; an attachment imported before keeps its old one.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict {}", path.display()))?;
+    }
+    Ok(file)
+}
+
+fn backup_attachment_id(account: &str, row_id: i64) -> AttachmentId {
+    let digits: String = account.chars().filter(char::is_ascii_digit).collect();
+    AttachmentId(format!("backup-{}-{}", digits, row_id))
+}
+
+impl Database {
+    /// Map message rows onto [`Message`]s for `account`.
+    ///
+    /// Handles the combined `message` table of current Signal versions as well
+    /// as the older split `sms`/`mms` tables.
+    fn messages(&self, account: &str) -> Vec<Message> {
+        let recipients: HashMap<i64, Recipient> = self
+            .rows("recipient")
+            .iter()
+            .filter_map(|row| Some((row.int(&["_id"])?, Recipient::from_row(row)?)))
+            .collect();
+        let threads: HashMap<i64, i64> = self
+            .rows("thread")
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    row.int(&["_id"])?,
+                    row.int(&["recipient_id", "thread_recipient_id", "recipient_ids"])?,
+                ))
+            })
+            .collect();
+        let me = Participant {
+            address: account.to_string(),
+            display_name: None,
+        };
+
+        let mut messages = Vec::new();
+        for (table, attachment_table, attachment_key) in [
+            ("message", "attachment", "message_id"),
+            ("mms", "part", "mid"),
+            ("sms", "", ""),
+        ] {
+            let attachments =
+                self.attachments_by_message(account, attachment_table, attachment_key);
+            let mentions = if attachment_table.is_empty() {
+                HashMap::new()
+            } else {
+                self.mentions_by_message(&recipients)
+            };
+
+            for row in self.rows(table) {
+                let message_type = row.int(&["type", "msg_box"]).unwrap_or_default();
+                let base_type = message_type & BASE_TYPE_MASK;
+                let outgoing = OUTGOING_BASE_TYPES.contains(&base_type);
+                if message_type & NON_CONTENT_BITS != 0
+                    || !(outgoing || base_type == BASE_INBOX_TYPE)
+                {
+                    continue;
+                }
+
+                let Some(thread) = row
+                    .int(&["thread_id"])
+                    .and_then(|thread| threads.get(&thread))
+                    .and_then(|recipient| recipients.get(recipient))
+                else {
+                    continue;
+                };
+                let author = if outgoing {
+                    me.clone()
+                } else {
+                    match row
+                        .int(&["from_recipient_id", "recipient_id", "address"])
+                        .and_then(|id| recipients.get(&id))
+                    {
+                        Some(recipient) => recipient.participant(),
+                        None => continue,
+                    }
+                };
+                let Some(sent) = row
+                    .int(&["date_sent", "date"])
+                    .and_then(DateTime::<Utc>::from_timestamp_millis)
+                else {
+                    continue;
+                };
+
+                let id = row.int(&["_id"]).unwrap_or_default();
+                let attachments = attachments.get(&id).cloned().unwrap_or_default();
+                let text = row.text(&["body"]).unwrap_or_default();
+                if text.is_empty() && attachments.is_empty() {
+                    continue;
+                }
+                messages.push(Message {
+                    id: MessageId(format!("{}:{}", author.address, sent.timestamp_millis())),
+                    conversation_id: thread.conversation_id(),
+                    author,
+                    timestamp: sent,
+                    body: MessageBody::Text {
+                        text: text.to_string(),
+                        mentions: mentions.get(&id).cloned().unwrap_or_default(),
+                        styles: Vec::new(),
+                    },
+                    attachments,
+                    // Android stores the timer in milliseconds.
+                    expires_in_seconds: row
+                        .int(&["expires_in"])
+                        .filter(|millis| *millis > 0)
+                        .and_then(|millis| u32::try_from(millis / 1000).ok()),
+                });
+            }
+        }
+        messages
+    }
+
+    fn attachments_by_message(
+        &self,
+        account: &str,
+        table: &str,
+        message_column: &str,
+    ) -> HashMap<i64, Vec<Attachment>> {
+        let mut attachments: HashMap<i64, Vec<Attachment>> = HashMap::new();
+        if table.is_empty() {
+            return attachments;
+        }
+        for row in self.rows(table) {
+            let (Some(row_id), Some(message)) = (row.int(&["_id"]), row.int(&[message_column]))
+            else {
+                continue;
+            };
+            attachments.entry(message).or_default().push(Attachment {
+                id: backup_attachment_id(account, row_id),
+                content_type: row
+                    .text(&["content_type", "ct"])
+                    .unwrap_or("application/octet-stream")
+                    .to_string(),
+                filename: row.text(&["file_name"]).map(str::to_string),
+                size_bytes: row
+                    .int(&["data_size"])
+                    .and_then(|size| u64::try_from(size).ok()),
+            });
+        }
+        attachments
+    }
+
+    fn mentions_by_message(
+        &self,
+        recipients: &HashMap<i64, Recipient>,
+    ) -> HashMap<i64, Vec<Mention>> {
+        let mut mentions: HashMap<i64, Vec<Mention>> = HashMap::new();
+        for row in self.rows("mention") {
+            let (Some(message), Some(recipient), Some(start), Some(length)) = (
+                row.int(&["message_id"]),
+                row.int(&["recipient_id"])
+                    .and_then(|id| recipients.get(&id)),
+                row.int(&["range_start"]),
+                row.int(&["range_length"]),
+            ) else {
+                continue;
+            };
+            mentions.entry(message).or_default().push(Mention {
+                start: start as u32,
+                length: length as u32,
+                participant: recipient.participant(),
+            });
+        }
+        mentions
+    }
+}
+
+/// A row of the `recipient` table: a contact or a group.
+#[derive(Debug)]
+struct Recipient {
+    address: String,
+    name: Option<String>,
+    group: bool,
+}
+
+impl Recipient {
+    fn from_row(row: &Row) -> Option<Self> {
+        let name = row
+            .text(&[
+                "system_joined_name",
+                "system_display_name",
+                "profile_joined_name",
+                "signal_profile_name",
+                "profile_given_name",
+            ])
+            .map(str::to_string);
+        if let Some(group) = row.text(&["group_id"]) {
+            return Some(Self {
+                address: signal_cli_group_id(group),
+                name,
+                group: true,
+            });
+        }
+        Some(Self {
+            address: row.text(&["e164", "phone", "aci", "uuid"])?.to_string(),
+            name,
+            group: false,
+        })
+    }
+
+    fn participant(&self) -> Participant {
+        Participant {
+            address: self.address.clone(),
+            display_name: if self.group { None } else { self.name.clone() },
+        }
+    }
+
+    fn conversation_id(&self) -> ConversationId {
+        ConversationId(self.address.clone())
+    }
+}
+
+/// Convert Android's `__signal_group__v2__!<hex>` into signal-cli's base64 group ID.
+fn signal_cli_group_id(group: &str) -> String {
+    let hex = GROUP_ID_PREFIXES
+        .iter()
+        .find_map(|prefix| group.strip_prefix(prefix))
+        .unwrap_or(group);
+    let bytes: Option<Vec<u8>> = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect();
+    match bytes {
+        Some(bytes) if !bytes.is_empty() => BASE64.encode(bytes),
+        _ => group.to_string(),
+    }
+}
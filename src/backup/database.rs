@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use super::frames::SqlValue;
+
+/// Tables the import reads; rows of every other table are dropped while parsing.
+const TABLES: &[&str] = &[
+    "recipient",
+    "thread",
+    "message",
+    "sms",
+    "mms",
+    "attachment",
+    "part",
+    "mention",
+];
+
+/// Rows of the relevant tables, rebuilt from the backup's `CREATE TABLE` and
+/// `INSERT` statements.
+#[derive(Debug, Default)]
+pub(super) struct Database {
+    columns: HashMap<String, Vec<String>>,
+    rows: HashMap<String, Vec<Row>>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Row {
+    values: HashMap<String, SqlValue>,
+}
+
+impl Database {
+    pub fn apply(&mut self, sql: &str, parameters: Vec<SqlValue>) {
+        let trimmed = sql.trim_start();
+        if starts_with_keywords(trimmed, &["CREATE", "TABLE"]) {
+            if let Some((table, columns)) = parse_create_table(trimmed) {
+                if TABLES.contains(&table.as_str()) {
+                    self.columns.insert(table, columns);
+                }
+            }
+        } else if starts_with_keywords(trimmed, &["INSERT", "INTO"]) {
+            let Some((table, listed)) = parse_insert(trimmed) else {
+                return;
+            };
+            if !TABLES.contains(&table.as_str()) {
+                return;
+            }
+            let Some(columns) = listed.as_ref().or_else(|| self.columns.get(&table)) else {
+                return;
+            };
+            let values = columns.iter().cloned().zip(parameters).collect();
+            self.rows.entry(table).or_default().push(Row { values });
+        }
+    }
+
+    pub fn rows(&self, table: &str) -> &[Row] {
+        self.rows.get(table).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+impl Row {
+    /// First non-empty text among `columns`, which lists a column's names across schema versions.
+    pub fn text(&self, columns: &[&str]) -> Option<&str> {
+        columns
+            .iter()
+            .find_map(|column| match self.values.get(*column) {
+                Some(SqlValue::Text(text)) if !text.is_empty() => Some(text.as_str()),
+                _ => None,
+            })
+    }
+
+    /// First integer among `columns`; numeric text is accepted as older schemas store some IDs as text.
+    pub fn int(&self, columns: &[&str]) -> Option<i64> {
+        columns
+            .iter()
+            .find_map(|column| match self.values.get(*column) {
+                Some(SqlValue::Integer(value)) => Some(*value),
+                Some(SqlValue::Real(value)) => Some(*value as i64),
+                Some(SqlValue::Text(text)) => text.trim().parse().ok(),
+                _ => None,
+            })
+    }
+}
+
+fn starts_with_keywords(sql: &str, keywords: &[&str]) -> bool {
+    let mut words = sql.split_whitespace();
+    keywords.iter().all(|keyword| {
+        words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+    })
+}
+
+/// `CREATE TABLE [IF NOT EXISTS] name (col type ..., constraint ...)`.
+fn parse_create_table(sql: &str) -> Option<(String, Vec<String>)> {
+    let open = sql.find('(')?;
+    let close = sql.rfind(')')?;
+    let head: Vec<&str> = sql[..open].split_whitespace().collect();
+    let table = unquote(head.last()?);
+
+    let columns = split_top_level(&sql[open + 1..close])
+        .into_iter()
+        .filter_map(|definition| {
+            let name = definition.split_whitespace().next()?;
+            let constraint = ["PRIMARY", "UNIQUE", "FOREIGN", "CHECK", "CONSTRAINT"]
+                .iter()
+                .any(|keyword| name.eq_ignore_ascii_case(keyword));
+            (!constraint).then(|| unquote(name))
+        })
+        .collect();
+    Some((table, columns))
+}
+
+/// `INSERT INTO name [(col, ...)] VALUES (?, ...)`, returning the listed columns if any.
+fn parse_insert(sql: &str) -> Option<(String, Option<Vec<String>>)> {
+    let rest = sql.trim_start()[6..].trim_start()[4..].trim_start();
+    let name_end = rest
+        .find(|ch: char| ch.is_whitespace() || ch == '(')
+        .unwrap_or(rest.len());
+    let table = unquote(&rest[..name_end]);
+    let rest = rest[name_end..].trim_start();
+
+    let listed = if rest.starts_with('(') {
+        let close = rest.find(')')?;
+        Some(
+            split_top_level(&rest[1..close])
+                .into_iter()
+                .map(|column| unquote(column.trim()))
+                .collect(),
+        )
+    } else {
+        None
+    };
+    Some((table, listed))
+}
+
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, ch) in list.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(list[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(list[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+fn unquote(name: &str) -> String {
+    name.trim_matches(|ch| matches!(ch, '"' | '`' | '[' | ']' | '\''))
+        .to_string()
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::settings::Overrides;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "signal-mcp-server",
+    version,
+    about = "MCP server for Signal via signal-cli"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Config file to read instead of searching ./config.toml and the XDG config directories.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Signal account (E.164) to serve, overriding `account`.
+    #[arg(long, global = true, value_name = "NUMBER")]
+    pub account: Option<String>,
+
+    /// Path to the signal-cli executable, overriding `signal_cli_path`.
+    #[arg(long = "signal-cli", global = true, value_name = "PATH")]
+    pub signal_cli: Option<PathBuf>,
+
+    /// Directory for the local message store, overriding `storage`.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub storage: Option<PathBuf>,
+
+    /// MCP transport, overriding `transport`.
+    #[arg(long, global = true, value_parser = ["stdio", "http"])]
+    pub transport: Option<String>,
+}
+
+impl Cli {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            config: self.config.clone(),
+            account: self.account.clone(),
+            signal_cli_path: self.signal_cli.clone(),
+            storage: self.storage.clone(),
+            transport: self.transport.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the MCP server (default).
+    Serve,
+    /// Verify signal-cli, the account, the daemon and storage, then exit.
+    Check,
+    /// Link as a secondary device by scanning a QR code with the primary phone.
+    Link {
+        /// Device name shown in the phone's linked devices list.
+        #[arg(long, default_value = "signal-mcp-server")]
+        name: String,
+    },
+    /// Register a phone number as a new primary account.
+    Register {
+        /// Phone number in E.164 format, e.g. +15551234567.
+        number: String,
+        /// Request the verification code by voice call instead of SMS.
+        #[arg(long)]
+        voice: bool,
+        /// `signalcaptcha://` token, required when Signal asks for a captcha.
+        #[arg(long)]
+        captcha: Option<String>,
+    },
+    /// Complete registration with the verification code received.
+    Verify {
+        /// Phone number passed to `register`.
+        number: String,
+        /// Verification code from the SMS or voice call.
+        code: String,
+        /// Registration lock PIN, if one is set on the account.
+        #[arg(long)]
+        pin: Option<String>,
+    },
+    /// Write a conversation's stored messages to a file under `storage/exports/`.
+    ///
+    /// Acts on the default account; pick another with `--account`.
+    Export {
+        /// Conversation ID: a contact's number or UUID, or a group ID.
+        conversation: String,
+        /// Output format; HTML inlines downloaded attachments.
+        #[arg(long, default_value = "markdown", value_parser = ["markdown", "jsonl", "html", "csv"])]
+        format: String,
+        /// Earliest message to include: YYYY-MM-DD or an RFC 3339 timestamp.
+        #[arg(long, value_name = "DATE")]
+        from: Option<String>,
+        /// Latest message to include: YYYY-MM-DD (whole day) or an RFC 3339 timestamp.
+        #[arg(long, value_name = "DATE")]
+        to: Option<String>,
+    },
+    /// Load messages and attachments from a Signal Android `.backup` file.
+    ///
+    /// Imports into the default account; pick another with `--account`.
+    Import {
+        /// Backup file, e.g. `signal-2024-01-01-00-00-00.backup`.
+        backup: PathBuf,
+        /// File holding the 30-digit backup passphrase; prompted on stdin otherwise.
+        #[arg(long, value_name = "PATH")]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Securely delete a conversation's stored messages and attachments.
+    ///
+    /// Acts on the default account; pick another with `--account`.
+    Purge {
+        /// Conversation ID: a contact's number or UUID, or a group ID.
+        conversation: String,
+    },
+}
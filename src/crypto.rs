@@ -0,0 +1,382 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::info;
+
+use crate::settings::{EncryptionSettings, Settings};
+
+/// Key parameters written next to the encrypted store.
+const HEADER_FILE: &str = "encryption.json";
+/// Known plaintext sealed into the header so a wrong key is caught at startup.
+const KEY_CHECK: &[u8] = b"signal-mcp-server store key";
+const NONCE_LEN: usize = 24;
+/// HKDF label separating the file-name key from the record key.
+const FILE_NAME_INFO: &[u8] = b"signal-mcp-server file names";
+/// Prefix of files sealed whole by [`StoreKey::seal_file`], such as attachments.
+const SEALED_FILE_MAGIC: &[u8] = b"signal-mcp-server sealed v1\n";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Symmetric key for the local store, derived with scrypt from the configured secret.
+pub struct StoreKey {
+    cipher: XChaCha20Poly1305,
+    /// Keys the HMAC that names files, so names reveal nothing about their contents.
+    file_names: [u8; 32],
+}
+
+impl std::fmt::Debug for StoreKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StoreKey(..)")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    kdf: String,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+    check: String,
+}
+
+impl StoreKey {
+    /// Load the key for `settings.storage`, failing closed when it cannot be used.
+    ///
+    /// Returns `None` only when encryption is not configured and the store has
+    /// never been encrypted. The first start with encryption writes a header
+    /// with a fresh salt; later starts verify the derived key against it.
+    pub fn open(settings: &Settings) -> Result<Option<Self>> {
+        let header_path = settings.storage.join(HEADER_FILE);
+        let header = read_header(&header_path)?;
+
+        let Some(encryption) = &settings.encryption else {
+            if header.is_some() {
+                bail!(
+                    "{} is encrypted but no `[encryption]` key_file or passphrase_file is configured",
+                    settings.storage.display()
+                );
+            }
+            return Ok(None);
+        };
+
+        let secret = read_secret(encryption)?;
+        match header {
+            Some(header) => {
+                let key = Self::derive(&secret, &header)?;
+                let check = BASE64
+                    .decode(&header.check)
+                    .context("corrupt key check in store header")?;
+                match key.decrypt(&check) {
+                    Ok(plaintext) if plaintext == KEY_CHECK => Ok(Some(key)),
+                    _ => bail!(
+                        "the configured key does not unlock {}; check key_file/passphrase_file",
+                        settings.storage.display()
+                    ),
+                }
+            }
+            None => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                let mut header = Header {
+                    version: 1,
+                    kdf: "scrypt".to_string(),
+                    log_n: 15,
+                    r: 8,
+                    p: 1,
+                    salt: BASE64.encode(salt),
+                    check: String::new(),
+                };
+                let key = Self::derive(&secret, &header)?;
+                header.check = BASE64.encode(key.encrypt(KEY_CHECK)?);
+
+                std::fs::create_dir_all(&settings.storage)
+                    .with_context(|| format!("failed to create {}", settings.storage.display()))?;
+                let encoded =
+                    serde_json::to_vec_pretty(&header).context("failed to encode store header")?;
+                std::fs::write(&header_path, encoded)
+                    .with_context(|| format!("failed to write {}", header_path.display()))?;
+                info!(path = %header_path.display(), "initialised store encryption");
+                Ok(Some(key))
+            }
+        }
+    }
+
+    fn derive(secret: &[u8], header: &Header) -> Result<Self> {
+        if header.version != 1 || header.kdf != "scrypt" {
+            bail!(
+                "unsupported store header version {} ({})",
+                header.version,
+                header.kdf
+            );
+        }
+        let salt = BASE64
+            .decode(&header.salt)
+            .context("corrupt salt in store header")?;
+        let params = scrypt::Params::new(header.log_n, header.r, header.p, 32)
+            .map_err(|err| anyhow!("invalid scrypt parameters in store header: {}", err))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(secret, &salt, &params, &mut key)
+            .map_err(|err| anyhow!("key derivation failed: {}", err))?;
+
+        let mut file_names = [0u8; 32];
+        Hkdf::<Sha256>::new(None, &key)
+            .expand(FILE_NAME_INFO, &mut file_names)
+            .map_err(|_| anyhow!("file-name key derivation failed"))?;
+
+        Ok(Self {
+            cipher: XChaCha20Poly1305::new(&Key::from(key)),
+            file_names,
+        })
+    }
+
+    /// Hex HMAC-SHA256 of `id`, used as the on-disk name for data about `id`.
+    pub fn file_name(&self, id: &str) -> String {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.file_names)
+            .expect("HMAC accepts any key length");
+        mac.update(id.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Seal `plaintext` as `nonce || ciphertext`.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("encryption failed"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let Some((nonce, ciphertext)) = sealed.split_first_chunk::<NONCE_LEN>() else {
+            bail!("encrypted record is truncated");
+        };
+        self.cipher
+            .decrypt(&XNonce::from(*nonce), ciphertext)
+            .map_err(|_| anyhow!("encrypted record failed authentication"))
+    }
+
+    /// Encrypt one store line, encoded as base64 so records stay newline-delimited.
+    pub fn seal_line(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(BASE64.encode(self.encrypt(plaintext)?).into_bytes())
+    }
+
+    pub fn open_line(&self, line: &str) -> Result<Vec<u8>> {
+        let sealed = BASE64
+            .decode(line.trim())
+            .context("encrypted record is not valid base64")?;
+        self.decrypt(&sealed)
+    }
+
+    /// Encrypt a whole file, marked so [`is_sealed_file`] recognises it.
+    pub fn seal_file(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut sealed = SEALED_FILE_MAGIC.to_vec();
+        sealed.extend(self.encrypt(plaintext)?);
+        Ok(sealed)
+    }
+
+    pub fn open_file(&self, contents: &[u8]) -> Result<Vec<u8>> {
+        let sealed = contents
+            .strip_prefix(SEALED_FILE_MAGIC)
+            .ok_or_else(|| anyhow!("file was not sealed by signal-mcp-server"))?;
+        self.decrypt(sealed)
+    }
+}
+
+/// Whether `contents` is a file written by [`StoreKey::seal_file`].
+pub fn is_sealed_file(contents: &[u8]) -> bool {
+    contents.starts_with(SEALED_FILE_MAGIC)
+}
+
+fn read_header(path: &Path) -> Result<Option<Header>> {
+    match std::fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .with_context(|| format!("corrupt store header {}", path.display()))
+            .map(Some),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn read_secret(encryption: &EncryptionSettings) -> Result<Vec<u8>> {
+    let (path, trim) = match (&encryption.key_file, &encryption.passphrase_file) {
+        (Some(path), None) => (path, false),
+        (None, Some(path)) => (path, true),
+        _ => bail!("set exactly one of encryption.key_file or encryption.passphrase_file"),
+    };
+    let contents =
+        std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let secret = if trim {
+        String::from_utf8(contents)
+            .with_context(|| format!("{} is not valid UTF-8", path.display()))?
+            .trim_end_matches(['\r', '\n'])
+            .as_bytes()
+            .to_vec()
+    } else {
+        contents
+    };
+    if secret.is_empty() {
+        bail!("{} is empty", path.display());
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+impl StoreKey {
+    /// A key derived with cheap scrypt parameters, for tests that do not exercise [`Self::open`].
+    pub(crate) fn for_tests(secret: &[u8]) -> Self {
+        let header = Header {
+            version: 1,
+            kdf: "scrypt".to_string(),
+            log_n: 1,
+            r: 1,
+            p: 1,
+            salt: BASE64.encode(b"test salt"),
+            check: String::new(),
+        };
+        Self::derive(secret, &header).expect("test key derivation")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Temporary directory holding a store and its key files.
+    struct Scratch {
+        root: PathBuf,
+    }
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "signal-mcp-crypto-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self { root }
+        }
+
+        /// Settings for the store under this directory, keyed by `secret` when given.
+        fn settings(&self, secret: Option<&[u8]>) -> Settings {
+            let mut settings = serde_json::json!({ "storage": self.root.join("var") });
+            if let Some(secret) = secret {
+                let key_file = self.root.join("key");
+                std::fs::write(&key_file, secret).unwrap();
+                settings["encryption"] = serde_json::json!({ "key_file": key_file });
+            }
+            serde_json::from_value(settings).unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn sealed_records_and_files_round_trip() {
+        let key = StoreKey::for_tests(b"secret");
+
+        let sealed = key.encrypt(b"record").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"record");
+        assert_eq!(key.decrypt(&sealed).unwrap(), b"record");
+
+        let line = key.seal_line(br#"{"type":"message"}"#).unwrap();
+        assert!(!line.contains(&b'\n'));
+        assert!(!line.starts_with(b"{"));
+        let line = String::from_utf8(line).unwrap();
+        assert_eq!(key.open_line(&line).unwrap(), br#"{"type":"message"}"#);
+
+        let file = key.seal_file(b"attachment bytes").unwrap();
+        assert!(is_sealed_file(&file));
+        assert!(!is_sealed_file(b"attachment bytes"));
+        assert_eq!(key.open_file(&file).unwrap(), b"attachment bytes");
+    }
+
+    #[test]
+    fn sealing_twice_uses_fresh_nonces() {
+        let key = StoreKey::for_tests(b"secret");
+        assert_ne!(key.encrypt(b"same").unwrap(), key.encrypt(b"same").unwrap());
+    }
+
+    #[test]
+    fn file_names_are_keyed() {
+        let key = StoreKey::for_tests(b"secret");
+        let name = key.file_name("+15551234567");
+        assert_eq!(name.len(), 64);
+        assert!(name.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(name, key.file_name("+15551234567"));
+        assert_ne!(name, key.file_name("+15557654321"));
+        assert_ne!(
+            name,
+            StoreKey::for_tests(b"other").file_name("+15551234567")
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let key = StoreKey::for_tests(b"secret");
+        let sealed = key.encrypt(b"record").unwrap();
+        for index in [0, NONCE_LEN, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 0x01;
+            assert!(key.decrypt(&tampered).is_err(), "flipped byte {}", index);
+        }
+        assert!(key.decrypt(&sealed[..NONCE_LEN - 1]).is_err());
+        assert!(key.decrypt(&sealed[..sealed.len() - 1]).is_err());
+
+        let mut file = key.seal_file(b"attachment").unwrap();
+        *file.last_mut().unwrap() ^= 0x01;
+        assert!(key.open_file(&file).is_err());
+        assert!(key.open_file(b"plain attachment").is_err());
+
+        let other = StoreKey::for_tests(b"other");
+        assert!(other.decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn open_accepts_only_the_key_the_store_was_created_with() {
+        let scratch = Scratch::new("open");
+        assert!(StoreKey::open(&scratch.settings(None)).unwrap().is_none());
+
+        let first = StoreKey::open(&scratch.settings(Some(b"correct horse")))
+            .unwrap()
+            .expect("encryption is configured");
+        assert!(scratch.root.join("var").join(HEADER_FILE).exists());
+        let again = StoreKey::open(&scratch.settings(Some(b"correct horse")))
+            .unwrap()
+            .expect("encryption is configured");
+        let sealed = first.encrypt(b"record").unwrap();
+        assert_eq!(again.decrypt(&sealed).unwrap(), b"record");
+        assert_eq!(first.file_name("id"), again.file_name("id"));
+
+        let err = StoreKey::open(&scratch.settings(Some(b"battery staple"))).unwrap_err();
+        assert!(err.to_string().contains("does not unlock"), "{:#}", err);
+
+        let err = StoreKey::open(&scratch.settings(None)).unwrap_err();
+        assert!(err.to_string().contains("is encrypted"), "{:#}", err);
+    }
+}
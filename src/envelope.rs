@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::mcp::{
+    Attachment, AttachmentId, ConversationId, Mention, Message, MessageBody, MessageId,
+    Participant, TextStyle,
+};
+use crate::text::parse_style_name;
+
+/// Convert a signal-cli `receive` envelope into an [`Message`].
+///
+/// Only envelopes carrying a `dataMessage` are mapped; receipts, typing
+/// indicators and sync messages yield `None`. `directory` maps addresses to
+/// display names and is used to resolve mentions and authors.
+pub fn parse_envelope(envelope: &Value, directory: &HashMap<String, String>) -> Option<Message> {
+    let data = envelope.get("dataMessage")?;
+
+    let author_address = str_field(envelope, "sourceNumber")
+        .or_else(|| str_field(envelope, "source"))
+        .or_else(|| str_field(envelope, "sourceUuid"))?;
+    let author = Participant {
+        address: author_address.to_string(),
+        display_name: directory
+            .get(author_address)
+            .cloned()
+            .or_else(|| non_empty(str_field(envelope, "sourceName"))),
+    };
+
+    let timestamp_millis = data
+        .get("timestamp")
+        .or_else(|| envelope.get("timestamp"))
+        .and_then(Value::as_i64)?;
+    let timestamp = DateTime::<Utc>::from_timestamp_millis(timestamp_millis)?;
+
+    let conversation = data
+        .get("groupInfo")
+        .and_then(|group| str_field(group, "groupId"))
+        .unwrap_or(author_address);
+
+    Some(Message {
+        id: MessageId(format!("{}:{}", author.address, timestamp_millis)),
+        conversation_id: ConversationId(conversation.to_string()),
+        author,
+        timestamp,
+        body: parse_body(data, directory),
+        attachments: parse_attachments(data),
+        // signal-cli reports 0 when the conversation has no timer.
+        expires_in_seconds: data
+            .get("expiresInSeconds")
+            .and_then(Value::as_u64)
+            .filter(|seconds| *seconds > 0)
+            .and_then(|seconds| u32::try_from(seconds).ok()),
+    })
+}
+
+fn parse_body(data: &Value, directory: &HashMap<String, String>) -> MessageBody {
+    if let Some(text) = str_field(data, "message") {
+        let mentions = data
+            .get("mentions")
+            .and_then(Value::as_array)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| parse_mention(entry, directory))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let styles = data
+            .get("textStyles")
+            .and_then(Value::as_array)
+            .map(|entries| entries.iter().filter_map(parse_text_style).collect())
+            .unwrap_or_default();
+
+        return MessageBody::Text {
+            text: text.to_string(),
+            mentions,
+            styles,
+        };
+    }
+
+    if let Some(sticker) = data.get("sticker") {
+        let pack_id = str_field(sticker, "packId").and_then(|id| Uuid::parse_str(id).ok());
+        let sticker_id = sticker
+            .get("stickerId")
+            .and_then(Value::as_u64)
+            .and_then(|id| u32::try_from(id).ok());
+        if let (Some(pack_id), Some(sticker_id)) = (pack_id, sticker_id) {
+            return MessageBody::Sticker {
+                pack_id,
+                sticker_id,
+            };
+        }
+    }
+
+    MessageBody::Unknown
+}
+
+fn parse_mention(entry: &Value, directory: &HashMap<String, String>) -> Option<Mention> {
+    let address = str_field(entry, "number").or_else(|| str_field(entry, "uuid"))?;
+    let start = entry.get("start").and_then(Value::as_u64)?;
+    let length = entry.get("length").and_then(Value::as_u64)?;
+
+    let display_name = directory
+        .get(address)
+        .cloned()
+        .or_else(|| non_empty(str_field(entry, "name")).filter(|name| name.as_str() != address));
+
+    Some(Mention {
+        start: u32::try_from(start).ok()?,
+        length: u32::try_from(length).ok()?,
+        participant: Participant {
+            address: address.to_string(),
+            display_name,
+        },
+    })
+}
+
+fn parse_text_style(entry: &Value) -> Option<TextStyle> {
+    let style = str_field(entry, "style").and_then(parse_style_name)?;
+    let start = entry.get("start").and_then(Value::as_u64)?;
+    let length = entry.get("length").and_then(Value::as_u64)?;
+
+    Some(TextStyle {
+        start: u32::try_from(start).ok()?,
+        length: u32::try_from(length).ok()?,
+        style,
+    })
+}
+
+fn parse_attachments(data: &Value) -> Vec<Attachment> {
+    data.get("attachments")
+        .and_then(Value::as_array)
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    Some(Attachment {
+                        id: AttachmentId(str_field(entry, "id")?.to_string()),
+                        content_type: str_field(entry, "contentType")
+                            .unwrap_or("application/octet-stream")
+                            .to_string(),
+                        filename: non_empty(str_field(entry, "filename")),
+                        size_bytes: entry.get("size").and_then(Value::as_u64),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::mcp::TextStyleKind;
+
+    fn directory() -> HashMap<String, String> {
+        HashMap::from([("+15550000002".to_string(), "Bea".to_string())])
+    }
+
+    fn text_body(message: &Message) -> (&str, &[Mention], &[TextStyle]) {
+        match &message.body {
+            MessageBody::Text {
+                text,
+                mentions,
+                styles,
+            } => (text, mentions, styles),
+            other => panic!("expected a text body, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn mentions_and_styles_keep_their_utf16_offsets() {
+        let envelope = json!({
+            "sourceNumber": "+15550000001",
+            "sourceName": "Ann",
+            "timestamp": 1_700_000_000_000i64,
+            "dataMessage": {
+                "timestamp": 1_700_000_000_000i64,
+                "message": "😀 \u{fffc} and \u{fffc} look",
+                "groupInfo": { "groupId": "Z3JvdXA=" },
+                "mentions": [
+                    { "number": "+15550000002", "name": "+15550000002", "start": 3, "length": 1 },
+                    { "uuid": "4c1f2a7e-9b3d-4e5f-8a6b-1c2d3e4f5a6b", "name": "Cy", "start": 9, "length": 1 },
+                    { "number": "+15550000004", "start": 2 }
+                ],
+                "textStyles": [
+                    { "style": "BOLD", "start": 0, "length": 4 },
+                    { "style": "italic", "start": 11, "length": 4 },
+                    { "style": "BLINK", "start": 0, "length": 1 }
+                ]
+            }
+        });
+        let message = parse_envelope(&envelope, &directory()).expect("data message");
+        assert_eq!(message.id.0, "+15550000001:1700000000000");
+        assert_eq!(message.conversation_id.0, "Z3JvdXA=");
+        assert_eq!(message.author.display_name.as_deref(), Some("Ann"));
+
+        let (text, mentions, styles) = text_body(&message);
+        assert_eq!(text, "😀 \u{fffc} and \u{fffc} look");
+        let mention_spans: Vec<_> = mentions
+            .iter()
+            .map(|m| {
+                let name = m.participant.display_name.as_deref();
+                (m.start, m.length, m.participant.address.as_str(), name)
+            })
+            .collect();
+        assert_eq!(
+            mention_spans,
+            [
+                (3, 1, "+15550000002", Some("Bea")),
+                (9, 1, "4c1f2a7e-9b3d-4e5f-8a6b-1c2d3e4f5a6b", Some("Cy")),
+            ]
+        );
+        let style_spans: Vec<_> = styles
+            .iter()
+            .map(|s| (s.start, s.length, s.style))
+            .collect();
+        assert_eq!(
+            style_spans,
+            [(0, 4, TextStyleKind::Bold), (11, 4, TextStyleKind::Italic)]
+        );
+        assert_eq!(
+            crate::text::render_markdown(text, mentions, styles),
+            "**😀 @Bea** and @Cy _look_"
+        );
+    }
+
+    #[test]
+    fn plain_messages_have_no_mentions_or_styles() {
+        let envelope = json!({
+            "sourceUuid": "4c1f2a7e-9b3d-4e5f-8a6b-1c2d3e4f5a6b",
+            "timestamp": 1_700_000_000_000i64,
+            "dataMessage": { "message": "hi", "expiresInSeconds": 0 }
+        });
+        let message = parse_envelope(&envelope, &HashMap::new()).expect("data message");
+        assert_eq!(
+            message.conversation_id.0,
+            "4c1f2a7e-9b3d-4e5f-8a6b-1c2d3e4f5a6b"
+        );
+        assert_eq!(message.author.display_name, None);
+        assert_eq!(message.expires_in_seconds, None);
+        let (text, mentions, styles) = text_body(&message);
+        assert_eq!(text, "hi");
+        assert!(mentions.is_empty());
+        assert!(styles.is_empty());
+    }
+
+    #[test]
+    fn envelopes_without_a_data_message_are_skipped() {
+        let receipt = json!({
+            "sourceNumber": "+15550000001",
+            "timestamp": 1_700_000_000_000i64,
+            "receiptMessage": { "isDelivery": true, "timestamps": [1] }
+        });
+        assert!(parse_envelope(&receipt, &HashMap::new()).is_none());
+    }
+}
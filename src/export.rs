@@ -0,0 +1,574 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use tokio::fs;
+use tracing::{info, warn};
+
+use crate::crypto::{is_sealed_file, StoreKey};
+use crate::mcp::{Attachment, ConversationId, Message, MessageBody};
+use crate::settings::{RetentionSettings, Settings};
+use crate::store::{Store, StoredEvent};
+use crate::text::render_markdown;
+
+/// Attachments larger than this are linked by name instead of inlined into HTML.
+const MAX_INLINE_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+    Jsonl,
+    Html,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Html => "html",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown",
+            ExportFormat::Jsonl => "application/jsonl",
+            ExportFormat::Html => "text/html",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "html" => Ok(ExportFormat::Html),
+            "csv" => Ok(ExportFormat::Csv),
+            other => bail!(
+                "unknown export format {:?}; expected markdown, jsonl, html or csv",
+                other
+            ),
+        }
+    }
+}
+
+/// Inclusive time window of messages to export; open ends are unbounded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ExportRange {
+    /// Parse `from`/`to` given as RFC 3339 timestamps or `YYYY-MM-DD` dates.
+    ///
+    /// A bare `to` date includes that whole day.
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self> {
+        let range = Self {
+            from: from.map(|value| parse_bound(value, false)).transpose()?,
+            to: to.map(|value| parse_bound(value, true)).transpose()?,
+        };
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                bail!("`from` ({}) is after `to` ({})", from, to);
+            }
+        }
+        Ok(range)
+    }
+
+    fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp <= to)
+    }
+}
+
+/// A rendered export and where it went.
+#[derive(Debug, Clone)]
+pub struct ExportedFile {
+    pub destination: ExportDestination,
+    pub format: ExportFormat,
+    pub messages: usize,
+    pub contents: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExportDestination {
+    /// Written under `storage/exports/`.
+    Saved {
+        path: PathBuf,
+        /// Path relative to the exports directory, `<account>/<file>`.
+        name: String,
+    },
+    /// Only returned to the caller, because a file would outlive the store's protections.
+    Inline { reason: &'static str },
+}
+
+/// Directory exports for all accounts are written to.
+pub fn exports_dir(settings: &Settings) -> PathBuf {
+    settings.storage.join("exports")
+}
+
+/// Render the stored messages of `conversation` within `range` and write them to disk.
+///
+/// Export files are neither encrypted nor pruned, so nothing is written when
+/// the store is encrypted, `retention` sets limits, or a message has a
+/// disappearing timer; the export is then returned inline only.
+pub async fn export_conversation(
+    settings: &Settings,
+    retention: &RetentionSettings,
+    account: &str,
+    store: &Store,
+    conversation: &ConversationId,
+    format: ExportFormat,
+    range: ExportRange,
+) -> Result<ExportedFile> {
+    let messages = stored_messages(store, conversation, range).await?;
+
+    let contents = match format {
+        ExportFormat::Markdown => render_markdown_transcript(
+            conversation,
+            &messages,
+            &describe_export(messages.len(), range),
+        ),
+        ExportFormat::Jsonl => render_jsonl(&messages)?,
+        ExportFormat::Html => {
+            render_html(
+                conversation,
+                &messages,
+                range,
+                &settings.attachment_directory(),
+                store.key().map(|key| key.as_ref()),
+            )
+            .await
+        }
+        ExportFormat::Csv => render_csv(&messages),
+    };
+
+    let inline_reason = if settings.encryption.is_some() {
+        Some("the store is encrypted and export files would not be")
+    } else if retention.is_enabled() {
+        Some("retention limits are configured and export files would not be pruned")
+    } else if messages
+        .iter()
+        .any(|message| message.expires_in_seconds.is_some())
+    {
+        Some("it contains disappearing messages and an export file would outlive their timer")
+    } else {
+        None
+    };
+    if let Some(reason) = inline_reason {
+        info!(
+            messages = messages.len(),
+            reason, "conversation exported inline"
+        );
+        return Ok(ExportedFile {
+            destination: ExportDestination::Inline { reason },
+            format,
+            messages: messages.len(),
+            contents,
+        });
+    }
+
+    let directory = exports_dir(settings).join(account);
+    fs::create_dir_all(&directory)
+        .await
+        .with_context(|| format!("failed to create {}", directory.display()))?;
+    let file_name = format!(
+        "{}-{}.{}",
+        safe_file_stem(&conversation.0),
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        format.extension()
+    );
+    let path = directory.join(&file_name);
+    fs::write(&path, &contents)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    info!(path = %path.display(), messages = messages.len(), "conversation exported");
+
+    Ok(ExportedFile {
+        destination: ExportDestination::Saved {
+            path,
+            name: format!("{}/{}", account, file_name),
+        },
+        format,
+        messages: messages.len(),
+        contents,
+    })
+}
+
+/// Markdown transcript of the latest `limit` stored messages of `conversation`.
+pub async fn conversation_transcript(
+    store: &Store,
+    conversation: &ConversationId,
+    limit: usize,
+) -> Result<String> {
+    let mut messages = stored_messages(store, conversation, ExportRange::default()).await?;
+    let omitted = messages.len().saturating_sub(limit);
+    messages.drain(..omitted);
+
+    let mut description = format!("Latest {} stored message(s)", messages.len());
+    if omitted > 0 {
+        let _ = write!(description, "; {} earlier message(s) omitted", omitted);
+    }
+    Ok(render_markdown_transcript(
+        conversation,
+        &messages,
+        &description,
+    ))
+}
+
+/// Names (`<account>/<file>`) of every export on disk, sorted by name.
+pub async fn list_exports(settings: &Settings) -> Result<Vec<String>> {
+    let root = exports_dir(settings);
+    let mut names = Vec::new();
+    let mut accounts = match fs::read_dir(&root).await {
+        Ok(accounts) => accounts,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+        Err(err) => return Err(err).with_context(|| format!("failed to list {}", root.display())),
+    };
+    while let Some(account) = accounts.next_entry().await? {
+        if !account.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut files = fs::read_dir(account.path()).await?;
+        while let Some(file) = files.next_entry().await? {
+            if let (Some(account), Some(file)) = (
+                account.file_name().to_str(),
+                file.file_name().to_str().map(str::to_string),
+            ) {
+                names.push(format!("{}/{}", account, file));
+            }
+        }
+    }
+    // File names end in the export timestamp, so this groups by conversation in time order.
+    names.sort();
+    Ok(names)
+}
+
+/// Read a previous export by the `<account>/<file>` name from [`ExportDestination::Saved`].
+pub async fn read_export(settings: &Settings, name: &str) -> Result<(String, ExportFormat)> {
+    let mut parts = name.split('/');
+    let (Some(account), Some(file), None) = (parts.next(), parts.next(), parts.next()) else {
+        bail!("export names have the form <account>/<file>");
+    };
+    if [account, file]
+        .iter()
+        .any(|part| part.is_empty() || part.starts_with('.') || part.contains('\\'))
+    {
+        bail!("invalid export name {:?}", name);
+    }
+    let format = Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| anyhow!("export {:?} has no extension", name))?
+        .parse::<ExportFormat>()?;
+    let path = exports_dir(settings).join(account).join(file);
+    let contents = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok((contents, format))
+}
+
+/// Stored messages of `conversation` within `range`, oldest first.
+async fn stored_messages(
+    store: &Store,
+    conversation: &ConversationId,
+    range: ExportRange,
+) -> Result<Vec<Message>> {
+    let mut messages: Vec<Message> = store
+        .events(conversation)
+        .await?
+        .into_iter()
+        .filter_map(|event| match event {
+            StoredEvent::Message(message) if range.contains(message.timestamp) => Some(message),
+            _ => None,
+        })
+        .collect();
+    messages.sort_by_key(|message| message.timestamp);
+    Ok(messages)
+}
+
+fn render_markdown_transcript(
+    conversation: &ConversationId,
+    messages: &[Message],
+    description: &str,
+) -> String {
+    let mut out = format!("# Signal conversation {}\n\n", conversation.0);
+    let _ = writeln!(out, "{}\n", description);
+    for message in messages {
+        let _ = writeln!(
+            out,
+            "**{}** — {}\n",
+            author_label(message),
+            message.timestamp.to_rfc3339()
+        );
+        let body = body_text(message);
+        if !body.is_empty() {
+            let _ = writeln!(out, "{}\n", body);
+        }
+        for attachment in &message.attachments {
+            let _ = writeln!(out, "- attachment: {}\n", attachment_label(attachment));
+        }
+    }
+    out
+}
+
+fn render_jsonl(messages: &[Message]) -> Result<String> {
+    let mut out = String::new();
+    for message in messages {
+        out.push_str(&serde_json::to_string(message).context("failed to encode message")?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+async fn render_html(
+    conversation: &ConversationId,
+    messages: &[Message],
+    range: ExportRange,
+    attachments_dir: &Path,
+    key: Option<&StoreKey>,
+) -> String {
+    let title = format!("Signal conversation {}", conversation.0);
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ font-family: sans-serif; max-width: 48rem; margin: 2rem auto; }}\n.message {{ border-bottom: 1px solid #ddd; padding: 0.5rem 0; }}\n.meta {{ color: #555; font-size: 0.85rem; }}\n.body {{ white-space: pre-wrap; }}\n.attachment img {{ max-width: 100%; }}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n<p class=\"meta\">{}</p>\n",
+        escape_html(&title),
+        escape_html(&title),
+        escape_html(&describe_export(messages.len(), range))
+    );
+    for message in messages {
+        let _ = write!(
+            out,
+            "<div class=\"message\">\n<div class=\"meta\"><strong>{}</strong> — <time datetime=\"{}\">{}</time></div>\n<div class=\"body\">{}</div>\n",
+            escape_html(&author_label(message)),
+            message.timestamp.to_rfc3339(),
+            message.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            escape_html(&body_text(message))
+        );
+        for attachment in &message.attachments {
+            out.push_str(&render_html_attachment(attachment, attachments_dir, key).await);
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Inline an attachment as a data URI so the export is a single self-contained file.
+async fn render_html_attachment(
+    attachment: &Attachment,
+    attachments_dir: &Path,
+    key: Option<&StoreKey>,
+) -> String {
+    let label = escape_html(&attachment_label(attachment));
+    let inline = if attachment.id.0.contains(['/', '\\']) || attachment.id.0.starts_with('.') {
+        None
+    } else {
+        read_inline_attachment(&attachments_dir.join(&attachment.id.0), key).await
+    };
+    let Some(bytes) = inline else {
+        return format!(
+            "<div class=\"attachment\">{} (not available locally)</div>\n",
+            label
+        );
+    };
+
+    let content_type = escape_html(&attachment.content_type);
+    let data = format!("data:{};base64,{}", content_type, BASE64.encode(bytes));
+    if attachment.content_type.starts_with("image/") {
+        format!(
+            "<div class=\"attachment\"><img src=\"{}\" alt=\"{}\"></div>\n",
+            data, label
+        )
+    } else {
+        let file_name = escape_html(
+            attachment
+                .filename
+                .as_deref()
+                .unwrap_or(attachment.id.0.as_str()),
+        );
+        format!(
+            "<div class=\"attachment\"><a href=\"{}\" download=\"{}\">{}</a></div>\n",
+            data, file_name, label
+        )
+    }
+}
+
+/// Read an attachment, opening it with `key` if signal-mcp-server sealed it.
+async fn read_inline_attachment(path: &Path, key: Option<&StoreKey>) -> Option<Vec<u8>> {
+    let metadata = fs::metadata(path).await.ok()?;
+    if metadata.len() > MAX_INLINE_ATTACHMENT_BYTES {
+        warn!(path = %path.display(), "attachment too large to inline into export");
+        return None;
+    }
+    let contents = fs::read(path).await.ok()?;
+    if !is_sealed_file(&contents) {
+        return Some(contents);
+    }
+    let Some(key) = key else {
+        warn!(path = %path.display(), "attachment is sealed but the store has no key");
+        return None;
+    };
+    match key.open_file(&contents) {
+        Ok(contents) => Some(contents),
+        Err(err) => {
+            warn!(?err, path = %path.display(), "failed to open sealed attachment");
+            None
+        }
+    }
+}
+
+fn render_csv(messages: &[Message]) -> String {
+    let mut out = String::from(
+        "timestamp,message_id,conversation_id,author_address,author_name,body,attachments\n",
+    );
+    for message in messages {
+        let attachments: Vec<String> = message.attachments.iter().map(attachment_label).collect();
+        let fields = [
+            message.timestamp.to_rfc3339(),
+            message.id.0.clone(),
+            message.conversation_id.0.clone(),
+            message.author.address.clone(),
+            message.author.display_name.clone().unwrap_or_default(),
+            neutralize_formula(body_text(message)),
+            attachments.join("; "),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn describe_export(count: usize, range: ExportRange) -> String {
+    let bound = |value: Option<DateTime<Utc>>| {
+        value
+            .map(|value| value.to_rfc3339())
+            .unwrap_or_else(|| "…".to_string())
+    };
+    let mut description = format!(
+        "Exported {} with {} message(s)",
+        Utc::now().format("%Y-%m-%d %H:%M UTC"),
+        count
+    );
+    if range.from.is_some() || range.to.is_some() {
+        let _ = write!(
+            description,
+            " from {} to {}",
+            bound(range.from),
+            bound(range.to)
+        );
+    }
+    description
+}
+
+fn author_label(message: &Message) -> String {
+    match &message.author.display_name {
+        Some(name) => format!("{} ({})", name, message.author.address),
+        None => message.author.address.clone(),
+    }
+}
+
+fn body_text(message: &Message) -> String {
+    match &message.body {
+        MessageBody::Text {
+            text,
+            mentions,
+            styles,
+        } => render_markdown(text, mentions, styles),
+        MessageBody::Sticker { .. } => "[sticker]".to_string(),
+        MessageBody::Unknown => "[unsupported message]".to_string(),
+    }
+}
+
+fn attachment_label(attachment: &Attachment) -> String {
+    format!(
+        "{} ({})",
+        attachment
+            .filename
+            .as_deref()
+            .unwrap_or(attachment.id.0.as_str()),
+        attachment.content_type
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Keep spreadsheets from evaluating message text that looks like a formula.
+fn neutralize_formula(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
+
+/// Conversation IDs include `+`, `/` and `=`; keep only characters safe in file names.
+fn safe_file_stem(value: &str) -> String {
+    value
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '+' || ch == '-' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn parse_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        anyhow!(
+            "invalid date {:?}; use YYYY-MM-DD or an RFC 3339 timestamp",
+            value
+        )
+    })?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    time.map(|time| time.and_utc())
+        .ok_or_else(|| anyhow!("invalid date {:?}", value))
+}
@@ -0,0 +1,292 @@
+use std::fmt;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+#[cfg(unix)]
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::process::Command;
+#[cfg(unix)]
+use tokio::time::timeout;
+
+use crate::settings::{self, AccountSettings, Settings, TimeoutSettings};
+use crate::signal_cli::{output_with_timeout, SignalCli};
+
+/// Oldest signal-cli release whose JSON output and flags this server relies on.
+pub const MIN_SIGNAL_CLI_VERSION: (u32, u32, u32) = (0, 13, 0);
+
+#[cfg(unix)]
+const DAEMON_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Pass,
+    Fail,
+    /// The check does not apply to this setup, e.g. no daemon is running.
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthItem {
+    pub name: String,
+    pub status: HealthStatus,
+    pub detail: String,
+}
+
+impl HealthItem {
+    fn from_result(name: impl Into<String>, result: Result<String>) -> Self {
+        let name = name.into();
+        match result {
+            Ok(detail) => Self {
+                name,
+                status: HealthStatus::Pass,
+                detail,
+            },
+            Err(err) => Self {
+                name,
+                status: HealthStatus::Fail,
+                detail: format!("{:#}", err),
+            },
+        }
+    }
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HealthStatus::Pass => "PASS",
+            HealthStatus::Fail => "FAIL",
+            HealthStatus::Skip => "SKIP",
+        })
+    }
+}
+
+/// Run every health check against `settings` for `accounts`, in a fixed order.
+///
+/// Checks never abort early: a missing executable still lets the storage
+/// check run, so one report shows everything that needs fixing.
+pub async fn run_checks(settings: &Settings, accounts: &[AccountSettings]) -> Vec<HealthItem> {
+    let mut items = vec![HealthItem::from_result(
+        "signal-cli executable",
+        check_executable(&settings.signal_cli_path),
+    )];
+    items.push(HealthItem::from_result(
+        "signal-cli version",
+        check_version(&settings.signal_cli_path, &settings.timeouts).await,
+    ));
+
+    if accounts.is_empty() {
+        items.push(HealthItem::from_result(
+            "account registered",
+            Err(anyhow!("no account configured")),
+        ));
+    }
+    for account in accounts {
+        items.push(HealthItem::from_result(
+            format!("account {} registered", account.alias),
+            check_registered(
+                &settings.signal_cli_path,
+                &account.number,
+                &settings.timeouts,
+            )
+            .await,
+        ));
+
+        let signal_cli = SignalCli::new(settings.signal_cli_path.clone(), account.number.clone())
+            .with_timeouts(settings.timeouts.clone());
+        items.push(HealthItem::from_result(
+            format!("account {} storage readable", account.alias),
+            signal_cli
+                .list_chats()
+                .await
+                .map(|chats| format!("{} contacts and groups readable", chats.len())),
+        ));
+    }
+
+    items.push(check_daemon(settings).await);
+    items.push(HealthItem::from_result(
+        "storage writable",
+        check_storage_writable(&settings.storage),
+    ));
+    items
+}
+
+pub fn all_passed(items: &[HealthItem]) -> bool {
+    items.iter().all(|item| item.status != HealthStatus::Fail)
+}
+
+pub fn format_report(items: &[HealthItem]) -> String {
+    items
+        .iter()
+        .map(|item| format!("[{}] {}: {}", item.status, item.name, item.detail))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn check_executable(path: &Path) -> Result<String> {
+    settings::check_executable(path)?;
+    Ok(path.display().to_string())
+}
+
+async fn check_version(path: &Path, timeouts: &TimeoutSettings) -> Result<String> {
+    let output = output_with_timeout(
+        Command::new(path).arg("--version"),
+        "--version",
+        timeouts.limit("version"),
+    )
+    .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "signal-cli --version failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reported = stdout.lines().next().unwrap_or_default().trim();
+    let version = parse_version(reported)
+        .ok_or_else(|| anyhow!("unrecognised version output {:?}", reported))?;
+    let (major, minor, patch) = MIN_SIGNAL_CLI_VERSION;
+    if version < MIN_SIGNAL_CLI_VERSION {
+        return Err(anyhow!(
+            "{} is older than the required {}.{}.{}",
+            reported,
+            major,
+            minor,
+            patch
+        ));
+    }
+    Ok(format!("{} (>= {}.{}.{})", reported, major, minor, patch))
+}
+
+/// Extract `major.minor.patch` from output such as `signal-cli 0.13.4`.
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let token = output
+        .split_whitespace()
+        .find(|token| token.starts_with(|c: char| c.is_ascii_digit()))?;
+    let mut parts = token
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+async fn check_registered(
+    executable: &Path,
+    account: &str,
+    timeouts: &TimeoutSettings,
+) -> Result<String> {
+    let output = output_with_timeout(
+        Command::new(executable).arg("listAccounts"),
+        "listAccounts",
+        timeouts.limit("listAccounts"),
+    )
+    .await?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "signal-cli listAccounts failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.split_whitespace().any(|token| token == account) {
+        Ok(format!("{} is registered with signal-cli", account))
+    } else {
+        Err(anyhow!(
+            "{} is not among the accounts known to signal-cli",
+            account
+        ))
+    }
+}
+
+const DAEMON_CHECK: &str = "daemon connectivity";
+
+#[cfg(unix)]
+async fn check_daemon(settings: &Settings) -> HealthItem {
+    let socket = match settings
+        .daemon_socket
+        .clone()
+        .or_else(default_daemon_socket)
+    {
+        Some(socket) if settings.daemon_socket.is_some() || socket.exists() => socket,
+        _ => {
+            return HealthItem {
+                name: DAEMON_CHECK.to_string(),
+                status: HealthStatus::Skip,
+                detail: "no daemon socket configured or found; signal-cli runs per request"
+                    .to_string(),
+            }
+        }
+    };
+
+    let result = timeout(DAEMON_TIMEOUT, probe_daemon(&socket))
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("timed out after {:?}", DAEMON_TIMEOUT)))
+        .with_context(|| format!("daemon at {}", socket.display()));
+    HealthItem::from_result(DAEMON_CHECK, result)
+}
+
+/// signal-cli's daemon only listens on a Unix socket, so there is nothing to probe.
+#[cfg(not(unix))]
+async fn check_daemon(_settings: &Settings) -> HealthItem {
+    HealthItem {
+        name: DAEMON_CHECK.to_string(),
+        status: HealthStatus::Skip,
+        detail: "unsupported on this platform; signal-cli runs per request".to_string(),
+    }
+}
+
+/// Send a JSON-RPC `version` request over the daemon socket and wait for the reply.
+#[cfg(unix)]
+async fn probe_daemon(socket: &Path) -> Result<String> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .context("failed to connect")?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"version\",\"id\":\"health\"}\n")
+        .await
+        .context("failed to send request")?;
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .read_line(&mut line)
+        .await
+        .context("failed to read response")?;
+    let response: serde_json::Value =
+        serde_json::from_str(line.trim()).context("invalid JSON-RPC response")?;
+    if let Some(error) = response.get("error") {
+        return Err(anyhow!("daemon returned error: {}", error));
+    }
+    let version = response
+        .pointer("/result/version")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or("unknown version");
+    Ok(format!("{} responded ({})", socket.display(), version))
+}
+
+#[cfg(unix)]
+fn default_daemon_socket() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime| PathBuf::from(runtime).join("signal-cli").join("socket"))
+}
+
+/// Create `storage` if needed and prove it writable by writing and removing a probe file.
+pub fn check_storage_writable(storage: &Path) -> Result<String> {
+    std::fs::create_dir_all(storage)
+        .with_context(|| format!("failed to create {}", storage.display()))?;
+    let probe = storage.join(".health-check");
+    std::fs::write(&probe, b"ok")
+        .with_context(|| format!("{} is not writable", storage.display()))?;
+    std::fs::remove_file(&probe)
+        .with_context(|| format!("failed to remove {}", probe.display()))?;
+    Ok(format!("{} is writable", storage.display()))
+}
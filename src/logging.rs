@@ -0,0 +1,339 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context as _, Result};
+use rust_mcp_sdk::schema::{LoggingLevel, LoggingMessageNotificationParams};
+use rust_mcp_sdk::McpServer;
+use serde_json::{Map, Value};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::field::{Field, Visit};
+use tracing::{warn, Event, Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+use crate::settings::{LogFormat, LogOutput, LogRotation, LoggingSettings, Settings};
+
+/// Level clients receive until they send `logging/setLevel`; enough to see why a tool failed.
+const DEFAULT_CLIENT_LEVEL: LoggingLevel = LoggingLevel::Warning;
+/// Severity stored while no client is listening, above every real level.
+const NOBODY_LISTENING: u8 = u8::MAX;
+const LOG_FILE_PREFIX: &str = "signal-mcp-server";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Install the global subscriber for the configured outputs, plus forwarding
+/// of this crate's events to MCP clients through the returned handle.
+///
+/// `settings` is `None` when the configuration could not be loaded; logs
+/// then go to stderr with the defaults. Outputs that fail to open are
+/// reported once logging is up, falling back to stderr if none remain.
+pub fn init(settings: Option<&Settings>) -> McpLogForwarder {
+    let defaults = LoggingSettings::default();
+    let logging = settings.map_or(&defaults, |settings| &settings.logging);
+    // RUST_LOG wins over the configured levels, as it always has.
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| logging.directives());
+    let filter = || {
+        EnvFilter::builder()
+            .parse(&directives)
+            .unwrap_or_else(|_| EnvFilter::new(&defaults.level))
+    };
+
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut failures = Vec::new();
+    for output in &logging.outputs {
+        let layer = match output {
+            LogOutput::Stderr => Ok(format_layer(
+                logging.format,
+                std::io::stderr,
+                std::io::stderr().is_terminal(),
+            )),
+            // Only reachable with loaded settings, as the defaults log to stderr alone.
+            LogOutput::File => settings
+                .map(Settings::log_directory)
+                .context("no log directory without a configuration")
+                .and_then(|directory| file_appender(logging, &directory))
+                .map(|appender| format_layer(logging.format, appender, false)),
+            LogOutput::Journald => tracing_journald::layer()
+                .map(|layer| {
+                    layer
+                        .with_syslog_identifier(LOG_FILE_PREFIX.to_string())
+                        .boxed()
+                })
+                .context("failed to connect to journald"),
+        };
+        match layer {
+            Ok(layer) => layers.push(layer.with_filter(filter()).boxed()),
+            Err(err) => failures.push((*output, err)),
+        }
+    }
+    // Also covers `outputs = []`: stdout carries the protocol, so stderr is the only safe default.
+    if layers.is_empty() {
+        layers.push(
+            format_layer(
+                logging.format,
+                std::io::stderr,
+                std::io::stderr().is_terminal(),
+            )
+            .with_filter(filter())
+            .boxed(),
+        );
+    }
+
+    let forwarder = McpLogForwarder::default();
+    let crate_target = env!("CARGO_CRATE_NAME");
+    layers.push(
+        McpLogLayer {
+            forwarder: forwarder.clone(),
+        }
+        .with_filter(filter_fn(move |metadata| {
+            metadata.target().starts_with(crate_target)
+        }))
+        .boxed(),
+    );
+    tracing_subscriber::registry().with(layers).init();
+
+    for (output, err) in failures {
+        warn!(?output, error = %format!("{:#}", err), "log output unavailable");
+    }
+    forwarder
+}
+
+/// Human-readable or JSON lines written to `writer`.
+fn format_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+fn file_appender(
+    logging: &LoggingSettings,
+    directory: &std::path::Path,
+) -> Result<RollingFileAppender> {
+    let rotation = match logging.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("failed to create log directory {}", directory.display()))?;
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log");
+    if logging.max_files > 0 {
+        builder = builder.max_log_files(logging.max_files);
+    }
+    builder
+        .build(directory)
+        .with_context(|| format!("failed to open log directory {}", directory.display()))
+}
+
+/// Sends log records to every initialized MCP client at the level it asked for.
+#[derive(Clone, Default)]
+pub struct McpLogForwarder {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    clients: Mutex<Vec<Client>>,
+    /// Lowest severity any client wants, so events nobody receives are dropped early.
+    threshold: AtomicU8,
+    queue: Mutex<Option<UnboundedSender<Record>>>,
+}
+
+struct Client {
+    runtime: Arc<dyn McpServer>,
+    level: LoggingLevel,
+}
+
+struct Record {
+    level: LoggingLevel,
+    logger: String,
+    data: Value,
+}
+
+impl McpLogForwarder {
+    /// Start forwarding to a client once it has initialized.
+    pub fn attach(&self, runtime: Arc<dyn McpServer>) {
+        self.set_level(runtime, DEFAULT_CLIENT_LEVEL);
+    }
+
+    /// Apply a client's `logging/setLevel`.
+    pub fn set_level(&self, runtime: Arc<dyn McpServer>, level: LoggingLevel) {
+        let mut clients = self
+            .shared
+            .clients
+            .lock()
+            .expect("log client lock poisoned");
+        match clients
+            .iter_mut()
+            .find(|client| Arc::ptr_eq(&client.runtime, &runtime))
+        {
+            Some(client) => client.level = level,
+            None => clients.push(Client { runtime, level }),
+        }
+        self.shared.update_threshold(&clients);
+        drop(clients);
+
+        let mut queue = self.shared.queue.lock().expect("log queue lock poisoned");
+        if queue.is_none() {
+            let (sender, receiver) = unbounded_channel();
+            tokio::spawn(dispatch(self.shared.clone(), receiver));
+            *queue = Some(sender);
+        }
+    }
+
+    fn wants(&self, level: LoggingLevel) -> bool {
+        severity(level) >= self.shared.threshold.load(Ordering::Relaxed)
+    }
+
+    fn enqueue(&self, record: Record) {
+        if let Some(queue) = self
+            .shared
+            .queue
+            .lock()
+            .expect("log queue lock poisoned")
+            .as_ref()
+        {
+            let _ = queue.send(record);
+        }
+    }
+}
+
+impl Shared {
+    fn update_threshold(&self, clients: &[Client]) {
+        let threshold = clients
+            .iter()
+            .map(|client| severity(client.level))
+            .min()
+            .unwrap_or(NOBODY_LISTENING);
+        self.threshold.store(threshold, Ordering::Relaxed);
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self {
+            clients: Mutex::new(Vec::new()),
+            threshold: AtomicU8::new(NOBODY_LISTENING),
+            queue: Mutex::new(None),
+        }
+    }
+}
+
+/// Deliver queued records, dropping clients whose connection has gone away.
+///
+/// Nothing here may log through `tracing`, or a failing client would feed itself.
+async fn dispatch(shared: Arc<Shared>, mut receiver: UnboundedReceiver<Record>) {
+    while let Some(record) = receiver.recv().await {
+        let recipients: Vec<Arc<dyn McpServer>> = shared
+            .clients
+            .lock()
+            .expect("log client lock poisoned")
+            .iter()
+            .filter(|client| severity(record.level) >= severity(client.level))
+            .map(|client| client.runtime.clone())
+            .collect();
+
+        for runtime in recipients {
+            let params = LoggingMessageNotificationParams {
+                data: record.data.clone(),
+                level: record.level,
+                logger: Some(record.logger.clone()),
+            };
+            if runtime.send_logging_message(params).await.is_err() {
+                let mut clients = shared.clients.lock().expect("log client lock poisoned");
+                clients.retain(|client| !Arc::ptr_eq(&client.runtime, &runtime));
+                shared.update_threshold(&clients);
+            }
+        }
+    }
+}
+
+/// Turns tracing events into MCP log records.
+struct McpLogLayer {
+    forwarder: McpLogForwarder,
+}
+
+impl<S: Subscriber> Layer<S> for McpLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = logging_level(*event.metadata().level());
+        if !self.forwarder.wants(level) {
+            return;
+        }
+        let mut fields = FieldMap(Map::new());
+        event.record(&mut fields);
+        self.forwarder.enqueue(Record {
+            level,
+            logger: event.metadata().target().to_string(),
+            data: Value::Object(fields.0),
+        });
+    }
+}
+
+/// Collects an event's fields, including its `message`, as JSON.
+struct FieldMap(Map<String, Value>);
+
+impl Visit for FieldMap {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+}
+
+fn logging_level(level: Level) -> LoggingLevel {
+    match level {
+        Level::ERROR => LoggingLevel::Error,
+        Level::WARN => LoggingLevel::Warning,
+        Level::INFO => LoggingLevel::Info,
+        Level::DEBUG | Level::TRACE => LoggingLevel::Debug,
+    }
+}
+
+/// RFC 5424 order of MCP levels, least severe first.
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
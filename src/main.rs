@@ -1,22 +1,193 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::Parser;
 use tokio::signal;
+use tokio::sync::watch;
 use tracing::{error, info};
 
+mod backup;
+mod cli;
+mod crypto;
+mod envelope;
+mod export;
+mod health;
+mod logging;
 mod mcp;
+mod reload;
+mod retention;
 mod server;
 mod settings;
+mod setup;
 mod signal_cli;
+mod store;
+mod text;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    let cli = cli::Cli::parse();
+    let overrides = cli.overrides();
+    // Configuration errors resurface when the command loads it; until then logs use the defaults.
+    let logs = logging::init(
+        settings::Settings::load_without_account(&overrides)
+            .ok()
+            .as_ref(),
+    );
+    match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => serve(&overrides, logs).await,
+        cli::Command::Check => check(&overrides).await,
+        cli::Command::Link { name } => {
+            setup::link(
+                &settings::Settings::load_without_account(&overrides)?,
+                &name,
+            )
+            .await
+        }
+        cli::Command::Register {
+            number,
+            voice,
+            captcha,
+        } => {
+            let config = settings::Settings::load_without_account(&overrides)?;
+            setup::register(&config, &number, voice, captcha.as_deref()).await
+        }
+        cli::Command::Verify { number, code, pin } => {
+            let config = settings::Settings::load_without_account(&overrides)?;
+            setup::verify(&config, &number, &code, pin.as_deref()).await
+        }
+        cli::Command::Purge { conversation } => purge(&overrides, &conversation).await,
+        cli::Command::Import {
+            backup,
+            passphrase_file,
+        } => import(&overrides, &backup, passphrase_file.as_deref()).await,
+        cli::Command::Export {
+            conversation,
+            format,
+            from,
+            to,
+        } => {
+            let range = export::ExportRange::parse(from.as_deref(), to.as_deref())?;
+            export(&overrides, &conversation, format.parse()?, range).await
+        }
+    }
+}
+
+async fn import(
+    overrides: &settings::Overrides,
+    backup: &std::path::Path,
+    passphrase_file: Option<&std::path::Path>,
+) -> Result<()> {
+    let config = settings::Settings::load(overrides, settings::Purpose::StoreOnly)?;
+    let Some(account) = config.all_accounts().into_iter().next() else {
+        anyhow::bail!("no Signal account configured");
+    };
+    let passphrase = match passphrase_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?,
+        None => {
+            eprint!("Backup passphrase (30 digits): ");
+            let mut line = String::new();
+            std::io::stdin()
+                .read_line(&mut line)
+                .context("failed to read the passphrase from stdin")?;
+            line
+        }
+    };
+
+    let key = crypto::StoreKey::open(&config)?.map(std::sync::Arc::new);
+    let store = store::Store::open_account(&config.storage, &account.number, key).await?;
+    let summary =
+        backup::import_backup(&config, &account.number, &store, backup, &passphrase).await?;
+    println!(
+        "Imported {} message(s) and {} attachment(s) into {} conversation(s) for {}; skipped {} already stored and {} expired",
+        summary.messages,
+        summary.attachments,
+        summary.conversations,
+        account.number,
+        summary.duplicates,
+        summary.expired
+    );
+    Ok(())
+}
+
+async fn export(
+    overrides: &settings::Overrides,
+    conversation: &str,
+    format: export::ExportFormat,
+    range: export::ExportRange,
+) -> Result<()> {
+    let config = settings::Settings::load(overrides, settings::Purpose::StoreOnly)?;
+    let Some(account) = config.all_accounts().into_iter().next() else {
+        anyhow::bail!("no Signal account configured");
+    };
+    let key = crypto::StoreKey::open(&config)?.map(std::sync::Arc::new);
+    let store = store::Store::open_account(&config.storage, &account.number, key).await?;
+    let exported = export::export_conversation(
+        &config,
+        &config.retention,
+        &account.number,
+        &store,
+        &mcp::ConversationId(conversation.to_string()),
+        format,
+        range,
+    )
+    .await?;
+    match &exported.destination {
+        export::ExportDestination::Saved { path, .. } => {
+            println!(
+                "Exported {} message(s) to {}",
+                exported.messages,
+                path.display()
+            );
+        }
+        export::ExportDestination::Inline { reason } => {
+            eprintln!(
+                "Exported {} message(s); not written to disk because {}",
+                exported.messages, reason
+            );
+            print!("{}", exported.contents);
+        }
+    }
+    Ok(())
+}
+
+async fn purge(overrides: &settings::Overrides, conversation: &str) -> Result<()> {
+    let config = settings::Settings::load(overrides, settings::Purpose::StoreOnly)?;
+    let Some(account) = config.all_accounts().into_iter().next() else {
+        anyhow::bail!("no Signal account configured");
+    };
+    let key = crypto::StoreKey::open(&config)?.map(std::sync::Arc::new);
+    let store = store::Store::open_account(&config.storage, &account.number, key).await?;
+    let removed = retention::Retention::new(&config)
+        .purge(&store, &mcp::ConversationId(conversation.to_string()))
+        .await?;
+    println!(
+        "Purged {} stored event(s) and {} attachment(s) for {} on {}",
+        removed.events, removed.attachments, conversation, account.number
+    );
+    Ok(())
+}
+
+async fn check(overrides: &settings::Overrides) -> Result<()> {
+    let config = settings::Settings::load_without_account(overrides)?;
+    let items = health::run_checks(&config, &config.all_accounts()).await;
+    println!("{}", health::format_report(&items));
+    if !health::all_passed(&items) {
+        anyhow::bail!("one or more health checks failed");
+    }
+    Ok(())
+}
 
+async fn serve(overrides: &settings::Overrides, logs: logging::McpLogForwarder) -> Result<()> {
     info!("starting signal-mcp-server");
 
-    let config = settings::Settings::load()?;
-    let server = server::Server::new(config).await?;
+    let config = settings::Settings::load(overrides, settings::Purpose::Serve)?;
+    let (policy_tx, policy_rx) = watch::channel(config.runtime_policy());
+    let server = server::Server::new(config.clone(), policy_rx.clone(), logs).await?;
+    let _retention = retention::spawn(
+        retention::Retention::new(&config),
+        server.stores(),
+        policy_rx,
+    );
+    let _reloader = reload::spawn(overrides.clone(), config, policy_tx);
 
     tokio::select! {
         result = server.run() => {
@@ -25,15 +25,58 @@ pub struct Message {
     pub timestamp: DateTime<Utc>,
     pub body: MessageBody,
     pub attachments: Vec<Attachment>,
+    /// Disappearing-message timer set by the conversation, if any.
+    #[serde(default)]
+    pub expires_in_seconds: Option<u32>,
+}
+
+impl Message {
+    /// When a disappearing message must be gone, counted from its timestamp.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_in_seconds
+            .map(|seconds| self.timestamp + chrono::Duration::seconds(i64::from(seconds)))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageBody {
-    Text(String),
-    Sticker { pack_id: Uuid, sticker_id: u32 },
+    Text {
+        text: String,
+        mentions: Vec<Mention>,
+        styles: Vec<TextStyle>,
+    },
+    Sticker {
+        pack_id: Uuid,
+        sticker_id: u32,
+    },
     Unknown,
 }
 
+/// A mention range within a text body, expressed in UTF-16 code units as Signal does.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mention {
+    pub start: u32,
+    pub length: u32,
+    pub participant: Participant,
+}
+
+/// A styled range within a text body, expressed in UTF-16 code units.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextStyle {
+    pub start: u32,
+    pub length: u32,
+    pub style: TextStyleKind,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum TextStyleKind {
+    Bold,
+    Italic,
+    Spoiler,
+    Strikethrough,
+    Monospace,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attachment {
     pub id: AttachmentId,
@@ -0,0 +1,177 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::settings::{Overrides, Purpose, RuntimePolicy, Settings};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Source of reload requests besides file changes; there is none without Unix signals.
+#[cfg(unix)]
+type Hangup = Signal;
+#[cfg(not(unix))]
+type Hangup = std::convert::Infallible;
+
+/// Reload the configuration on SIGHUP (on Unix) or when the config file changes.
+///
+/// Only [`RuntimePolicy`] fields are applied; changes to anything else are
+/// logged and ignored until the next restart.
+pub fn spawn(
+    overrides: Overrides,
+    mut current: Settings,
+    policy: watch::Sender<RuntimePolicy>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut hangup = install_hangup();
+        let mut ticker = interval(POLL_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last_modified = modified_time(current.config_path.as_deref());
+
+        loop {
+            let trigger = tokio::select! {
+                _ = next_hangup(&mut hangup) => "SIGHUP",
+                _ = ticker.tick() => {
+                    let modified = modified_time(current.config_path.as_deref());
+                    if modified == last_modified {
+                        continue;
+                    }
+                    last_modified = modified;
+                    "config file change"
+                }
+            };
+            reload(&overrides, &mut current, &policy, trigger);
+        }
+    })
+}
+
+fn reload(
+    overrides: &Overrides,
+    current: &mut Settings,
+    policy: &watch::Sender<RuntimePolicy>,
+    trigger: &str,
+) {
+    let next = match Settings::load(overrides, Purpose::Serve) {
+        Ok(next) => next,
+        Err(err) => {
+            warn!(
+                trigger,
+                error = %format!("{:#}", err),
+                "configuration reload failed; keeping current settings"
+            );
+            return;
+        }
+    };
+
+    let rejected = immutable_changes(current, &next);
+    if !rejected.is_empty() {
+        warn!(
+            trigger,
+            fields = %rejected.join(", "),
+            "ignoring changes that only take effect after a restart"
+        );
+    }
+
+    let previous = policy.borrow().clone();
+    let updated = next.runtime_policy();
+    current.identity_policy = next.identity_policy;
+    current.retention = next.retention;
+    if previous == updated {
+        info!(trigger, "configuration reloaded; runtime policy unchanged");
+        return;
+    }
+    if previous.identity_policy != updated.identity_policy {
+        info!(
+            trigger,
+            from = ?previous.identity_policy,
+            to = ?updated.identity_policy,
+            "configuration reloaded; identity_policy updated"
+        );
+    }
+    if previous.retention != updated.retention {
+        info!(trigger, retention = ?updated.retention, "configuration reloaded; retention updated");
+    }
+    policy.send_replace(updated);
+}
+
+/// Names of settings that differ between `current` and `next` but cannot change live.
+fn immutable_changes(current: &Settings, next: &Settings) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    if current.all_accounts() != next.all_accounts() {
+        changed.push("account/accounts");
+    }
+    if current.signal_cli_path != next.signal_cli_path {
+        changed.push("signal_cli_path");
+    }
+    if current.storage != next.storage {
+        changed.push("storage");
+    }
+    if current.encryption != next.encryption {
+        changed.push("encryption");
+    }
+    if current.attachments_dir != next.attachments_dir {
+        changed.push("attachments_dir");
+    }
+    if current.avatars_dir != next.avatars_dir {
+        changed.push("avatars_dir");
+    }
+    if current.logging != next.logging {
+        changed.push("logging");
+    }
+    if current.timeouts != next.timeouts {
+        changed.push("timeouts");
+    }
+    if current.daemon_socket != next.daemon_socket {
+        changed.push("daemon_socket");
+    }
+    if current.transport != next.transport
+        || current.http_host != next.http_host
+        || current.http_port != next.http_port
+    {
+        changed.push("transport/http_host/http_port");
+    }
+    changed
+}
+
+#[cfg(unix)]
+fn install_hangup() -> Option<Hangup> {
+    match signal(SignalKind::hangup()) {
+        Ok(hangup) => Some(hangup),
+        Err(err) => {
+            warn!(
+                ?err,
+                "failed to install SIGHUP handler; reloading on file changes only"
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn install_hangup() -> Option<Hangup> {
+    info!("SIGHUP is not available on this platform; reloading on file changes only");
+    None
+}
+
+async fn next_hangup(hangup: &mut Option<Hangup>) {
+    match hangup {
+        #[cfg(unix)]
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        #[cfg(not(unix))]
+        Some(never) => match *never {},
+        None => std::future::pending().await,
+    }
+}
+
+fn modified_time(path: Option<&Path>) -> Option<SystemTime> {
+    std::fs::metadata(path?)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
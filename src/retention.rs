@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::mcp::{AttachmentId, ConversationId};
+use crate::settings::{RetentionSettings, RuntimePolicy, Settings};
+use crate::store::{shred_file, Store};
+
+/// How often stores are checked for disappearing messages past their timer.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Enforces retention limits and purges, removing attachments of dropped messages.
+#[derive(Debug, Clone)]
+pub struct Retention {
+    settings: RetentionSettings,
+    attachments_dir: PathBuf,
+}
+
+/// Totals for one retention pass, expiry pass or purge.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Removed {
+    pub events: usize,
+    pub attachments: usize,
+}
+
+impl Retention {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            settings: settings.retention.clone(),
+            attachments_dir: settings.attachment_directory(),
+        }
+    }
+
+    /// Apply the configured limits to every conversation in `store`.
+    ///
+    /// A conversation that cannot be pruned is logged and skipped so the
+    /// others are still held to their limits; the pass then fails naming
+    /// how many conversations were skipped.
+    pub async fn enforce(&self, store: &Store) -> Result<Removed> {
+        let mut removed = Removed::default();
+        if !self.settings.is_enabled() {
+            return Ok(removed);
+        }
+
+        let now = Utc::now();
+        let mut failed = 0;
+        for conversation in store.conversations().await? {
+            let rule = self.settings.rule_for(&conversation.0);
+            if rule.max_age_days.is_none() && rule.max_messages.is_none() {
+                continue;
+            }
+            let cutoff = rule
+                .max_age_days
+                .map(|days| now - chrono::Duration::days(i64::from(days)));
+            match store.retain(&conversation, cutoff, rule.max_messages).await {
+                Ok(pruned) => {
+                    removed.events += pruned.events;
+                    removed.attachments += self.remove_attachments(&pruned.attachments).await;
+                }
+                Err(err) => {
+                    failed += 1;
+                    warn!(
+                        conversation = %conversation.0,
+                        error = %format!("{:#}", err),
+                        "failed to apply retention limits"
+                    );
+                }
+            }
+        }
+        match failed {
+            0 => Ok(removed),
+            failed => Err(anyhow!(
+                "retention limits could not be applied to {} conversation(s)",
+                failed
+            )),
+        }
+    }
+
+    /// Delete disappearing messages in `store` whose timer has elapsed.
+    ///
+    /// Attachments of the messages that were deleted are removed even when
+    /// some conversations failed.
+    pub async fn expire(&self, store: &Store) -> Result<Removed> {
+        let (pruned, result) = store.expire_due(Utc::now()).await;
+        let removed = Removed {
+            events: pruned.events,
+            attachments: self.remove_attachments(&pruned.attachments).await,
+        };
+        result.map(|()| removed)
+    }
+
+    /// Securely delete everything stored for `conversation`.
+    pub async fn purge(&self, store: &Store, conversation: &ConversationId) -> Result<Removed> {
+        let pruned = store.purge(conversation).await?;
+        Ok(Removed {
+            events: pruned.events,
+            attachments: self.remove_attachments(&pruned.attachments).await,
+        })
+    }
+
+    /// Shred the downloaded copies of `attachments`, returning how many were removed.
+    async fn remove_attachments(&self, attachments: &[AttachmentId]) -> usize {
+        let mut removed = 0;
+        for attachment in attachments {
+            // IDs come from received envelopes; never let one escape the directory.
+            if attachment.0.is_empty()
+                || attachment.0.contains(['/', '\\'])
+                || attachment.0.starts_with('.')
+            {
+                warn!(id = %attachment.0, "skipping attachment with unexpected ID");
+                continue;
+            }
+            let path = self.attachments_dir.join(&attachment.0);
+            match shred_file(&path).await {
+                Ok(true) => removed += 1,
+                Ok(false) => {}
+                Err(err) => warn!(?err, path = %path.display(), "failed to remove attachment"),
+            }
+        }
+        removed
+    }
+}
+
+/// Run the retention rules against every store now and then every
+/// `interval_minutes`, and delete disappearing messages as their timers elapse.
+///
+/// Retention settings published on `policy` replace the current ones and
+/// restart the schedule, so a reloaded limit is applied straight away.
+pub fn spawn(
+    mut retention: Retention,
+    stores: Vec<Arc<Store>>,
+    mut policy: watch::Receiver<RuntimePolicy>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut limits = limits_interval(&retention.settings);
+        let mut expiry = interval(EXPIRY_INTERVAL);
+        expiry.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                Ok(()) = policy.changed() => {
+                    let settings = policy.borrow_and_update().retention.clone();
+                    if settings != retention.settings {
+                        retention.settings = settings;
+                        limits = limits_interval(&retention.settings);
+                    }
+                }
+                _ = limits.tick(), if retention.settings.is_enabled() => {
+                    for store in &stores {
+                        log_pass("retention", retention.enforce(store).await);
+                    }
+                }
+                _ = expiry.tick() => {
+                    for store in &stores {
+                        log_pass("disappearing-message expiry", retention.expire(store).await);
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn limits_interval(settings: &RetentionSettings) -> Interval {
+    let period = Duration::from_secs(settings.interval_minutes.max(1) * 60);
+    let mut limits = interval(period);
+    limits.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    limits
+}
+
+fn log_pass(job: &str, result: Result<Removed>) {
+    match result {
+        Ok(removed) if removed.events > 0 => info!(
+            job,
+            events = removed.events,
+            attachments = removed.attachments,
+            "removed stored history"
+        ),
+        Ok(_) => {}
+        Err(err) => warn!(job, error = %format!("{:#}", err), "store cleanup failed"),
+    }
+}
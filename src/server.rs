@@ -1,62 +1,180 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use rust_mcp_sdk::mcp_server::{server_runtime, ServerHandler};
+use chrono::Utc;
+use rust_mcp_sdk::mcp_server::{hyper_server, server_runtime, HyperServerOptions, ServerHandler};
 use rust_mcp_sdk::schema::schema_utils::CallToolError;
 use rust_mcp_sdk::schema::{
-    CallToolRequest, CallToolResult, Implementation, InitializeResult, ListResourcesRequest,
-    ListResourcesResult, ListToolsRequest, ListToolsResult, ReadResourceRequest,
-    ReadResourceResult, Resource, ServerCapabilities, ServerCapabilitiesResources,
-    ServerCapabilitiesTools, TextContent, TextResourceContents, Tool, ToolAnnotations,
-    ToolInputSchema, LATEST_PROTOCOL_VERSION,
+    CallToolRequest, CallToolRequestParams, CallToolResult, CancelledNotification, CompleteRequest,
+    CompleteResult, GetPromptRequest, GetPromptResult, Implementation, InitializeResult,
+    ListPromptsRequest, ListPromptsResult, ListResourceTemplatesRequest,
+    ListResourceTemplatesResult, ListResourcesRequest, ListResourcesResult, ListToolsRequest,
+    ListToolsResult, Prompt, ReadResourceRequest, ReadResourceResult, Resource, ServerCapabilities,
+    ServerCapabilitiesPrompts, ServerCapabilitiesResources, ServerCapabilitiesTools,
+    SetLevelRequest, TextContent, TextResourceContents, Tool, ToolAnnotations, ToolInputSchema,
+    LATEST_PROTOCOL_VERSION,
 };
 use rust_mcp_sdk::{McpServer, StdioTransport, TransportOptions};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::{Map, Value};
+use tokio::sync::watch;
 use tracing::{info, warn};
 
-use crate::settings::Settings;
-use crate::signal_cli::SignalCli;
+use crate::crypto::StoreKey;
+use crate::envelope::parse_envelope;
+use crate::export::{list_exports, read_export};
+use crate::logging::McpLogForwarder;
+use crate::mcp::{ConversationId, Message, MessageBody};
+use crate::retention::Retention;
+use crate::settings::{IdentityPolicy, RuntimePolicy, Settings, TransportKind};
+use crate::signal_cli::{is_group_recipient, ChatEntry, ChatKind, SignalCli, SignalCliError};
+use crate::store::{IdentityAction, IdentityChange, Store, StoredEvent};
+use crate::text::{
+    find_named_mentions, merge_mentions, parse_markdown, render_markdown, utf16_len, MentionSpan,
+};
+
+mod cancel;
+mod completion;
+mod contacts;
+mod devices;
+mod export;
+mod groups;
+mod health;
+mod identities;
+mod prompts;
+mod purge;
+mod wait;
+
+use cancel::{shielded, take_request_id, InFlightCalls, RequestIdTransport};
+use contacts::{build_contact_tools, CONTACT_TOOLS};
+use devices::{build_device_tools, DEVICE_TOOLS};
+use export::{build_export_tool, export_resource, EXPORT_CONVERSATION_TOOL, EXPORT_URI_PREFIX};
+use groups::{build_group_tools, GROUP_TOOLS};
+use health::{build_health_tool, HEALTH_TOOL};
+use identities::{build_identity_tools, IDENTITY_TOOLS, LIST_IDENTITIES_TOOL, TRUST_IDENTITY_TOOL};
+use prompts::{build_prompts, conversation_resource_template, CONVERSATION_URI_PREFIX};
+use purge::{build_purge_tool, PURGE_CONVERSATION_TOOL};
+use wait::{build_wait_for_message_tool, WAIT_FOR_MESSAGE_TOOL};
 
 const LIST_CONVERSATIONS_TOOL: &str = "signal_list_conversations";
 const SEND_MESSAGE_TOOL: &str = "signal_send_message";
+const RECEIVE_MESSAGES_TOOL: &str = "signal_receive_messages";
+const DEFAULT_RECEIVE_TIMEOUT_SECONDS: u64 = 1;
 const RESOURCE_OVERVIEW_URI: &str = "resource://signal/overview";
 
 pub struct Server {
     settings: Settings,
-    signal_cli: Arc<SignalCli>,
+    accounts: Vec<Arc<AccountHandler>>,
+    logs: McpLogForwarder,
 }
 
 impl Server {
-    pub async fn new(settings: Settings) -> Result<Self> {
+    pub async fn new(
+        settings: Settings,
+        policy: watch::Receiver<RuntimePolicy>,
+        logs: McpLogForwarder,
+    ) -> Result<Self> {
         info!("initializing server components");
-        let signal_cli = Arc::new(SignalCli::new(
-            settings.signal_cli_path.clone(),
-            settings.account.clone(),
-        ));
+        let configured = settings.all_accounts();
+        if let Some(primary) = configured.first() {
+            Store::adopt_legacy_layout(&settings.storage, &primary.number).await?;
+        }
+
+        let key = StoreKey::open(&settings)?.map(Arc::new);
+        let retention = Arc::new(Retention::new(&settings));
+        let mut accounts = Vec::with_capacity(configured.len());
+        for account in configured {
+            let signal_cli = Arc::new(
+                SignalCli::new(settings.signal_cli_path.clone(), account.number.clone())
+                    .with_policy(policy.clone())
+                    .with_timeouts(settings.timeouts.clone()),
+            );
+            let store = Arc::new(
+                Store::open_account(&settings.storage, &account.number, key.clone()).await?,
+            );
+            info!(alias = %account.alias, number = %account.number, "account configured");
+            accounts.push(Arc::new(AccountHandler {
+                alias: account.alias,
+                number: account.number,
+                signal_cli,
+                store,
+                retention: retention.clone(),
+                policy: policy.clone(),
+                avatars_dir: settings.avatars_dir.clone(),
+                completion_chats: tokio::sync::Mutex::new(None),
+            }));
+        }
+
         Ok(Self {
             settings,
-            signal_cli,
+            accounts,
+            logs,
         })
     }
 
-    pub async fn run(&self) -> Result<()> {
-        let transport = StdioTransport::new(TransportOptions::default())
-            .map_err(|err| anyhow!("failed to create stdio transport: {err}"))?;
+    /// Every account's store, for background jobs that run beside the server.
+    pub fn stores(&self) -> Vec<Arc<Store>> {
+        self.accounts
+            .iter()
+            .map(|account| account.store.clone())
+            .collect()
+    }
 
+    pub async fn run(&self) -> Result<()> {
         let server_details = self.build_server_details();
-        let handler = SignalMcpHandler::new(self.signal_cli.clone());
+        let handler = SignalMcpHandler::new(
+            self.accounts.clone(),
+            self.settings.clone(),
+            self.logs.clone(),
+        );
 
-        let runtime = server_runtime::create_server(server_details, transport, handler);
-        info!("signal MCP server runtime started; waiting for MCP client initialization");
+        match self.settings.transport {
+            TransportKind::Stdio => {
+                let transport = StdioTransport::new(TransportOptions::default())
+                    .map(RequestIdTransport::new)
+                    .map_err(|err| anyhow!("failed to create stdio transport: {err}"))?;
+                let runtime = server_runtime::create_server(server_details, transport, handler);
+                info!("signal MCP server runtime started; waiting for MCP client initialization");
 
-        runtime
-            .start()
-            .await
-            .map_err(|err| anyhow!("mcp runtime error: {err}"))
+                runtime
+                    .start()
+                    .await
+                    .map_err(|err| anyhow!("mcp runtime error: {err}"))
+            }
+            TransportKind::Http => {
+                if self.settings.http_allow_remote {
+                    warn!(
+                        host = %self.settings.http_host,
+                        "serving HTTP without authentication beyond loopback"
+                    );
+                }
+                let options = HyperServerOptions {
+                    host: self.settings.http_host.clone(),
+                    port: self.settings.http_port,
+                    sse_support: false,
+                    dns_rebinding_protection: true,
+                    allowed_hosts: Some(self.settings.http_allowed_hosts()),
+                    allowed_origins: Some(self.settings.http_allowed_origins()),
+                    ..Default::default()
+                };
+                let server = hyper_server::create_server(server_details, handler, options);
+                info!(
+                    host = %self.settings.http_host,
+                    port = self.settings.http_port,
+                    "signal MCP server listening for streamable HTTP clients"
+                );
+
+                server
+                    .start()
+                    .await
+                    .map_err(|err| anyhow!("mcp http server error: {err}"))
+            }
+        }
     }
 
     fn build_server_details(&self) -> InitializeResult {
@@ -82,27 +200,88 @@ impl Server {
             list_changed: Some(false),
             subscribe: Some(false),
         });
+        capabilities.prompts = Some(ServerCapabilitiesPrompts {
+            list_changed: Some(false),
+        });
+        capabilities.completions = Some(Map::new());
+        capabilities.logging = Some(Map::new());
         capabilities
     }
 
     fn server_instructions(&self) -> String {
         format!(
-            "Expose Signal conversations for account {}. Use `{}` to fetch metadata, `{}` to send messages, or read `{}` for setup guidance.",
-            self.settings.account, LIST_CONVERSATIONS_TOOL, SEND_MESSAGE_TOOL, RESOURCE_OVERVIEW_URI
+            "Expose Signal conversations for accounts {}. Every tool takes an optional `account` (alias or number) and defaults to the first. Use `{}` to fetch metadata, `{}` to send messages, `{}` to fetch new messages, `{}` to wait for a reply, or read `{}` for setup guidance.",
+            self.accounts
+                .iter()
+                .map(|account| format!("{} ({})", account.alias, account.number))
+                .collect::<Vec<_>>()
+                .join(", "),
+            LIST_CONVERSATIONS_TOOL,
+            SEND_MESSAGE_TOOL,
+            RECEIVE_MESSAGES_TOOL,
+            WAIT_FOR_MESSAGE_TOOL,
+            RESOURCE_OVERVIEW_URI
         )
     }
 }
 
 struct SignalMcpHandler {
-    signal_cli: Arc<SignalCli>,
+    accounts: Vec<Arc<AccountHandler>>,
+    settings: Settings,
     tools: Vec<Tool>,
     resources: Vec<ResourceEntry>,
+    prompts: Vec<Prompt>,
+    logs: McpLogForwarder,
+    in_flight: InFlightCalls,
+}
+
+/// Tool implementations bound to one Signal account and its namespaced store.
+struct AccountHandler {
+    alias: String,
+    number: String,
+    signal_cli: Arc<SignalCli>,
+    store: Arc<Store>,
+    retention: Arc<Retention>,
+    policy: watch::Receiver<RuntimePolicy>,
+    /// Only directory group avatars may be read from, if any.
+    avatars_dir: Option<PathBuf>,
+    /// Recent `listChats` result reused while an argument is being completed.
+    completion_chats: tokio::sync::Mutex<Option<(Instant, Vec<ChatEntry>)>>,
+}
+
+/// Argument every tool accepts to pick the account it runs against.
+#[derive(Debug, Default, Deserialize)]
+struct AccountArg {
+    account: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListConversationsArgs {
+    #[serde(default)]
+    include_blocked: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct SendMessageArgs {
     recipient: String,
     message: String,
+    #[serde(default)]
+    mentions: Vec<MentionSpan>,
+    #[serde(default)]
+    format: MessageFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MessageFormat {
+    #[default]
+    Markdown,
+    Plain,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ReceiveMessagesArgs {
+    timeout_seconds: Option<u64>,
 }
 
 struct ResourceEntry {
@@ -111,21 +290,129 @@ struct ResourceEntry {
 }
 
 impl SignalMcpHandler {
-    fn new(signal_cli: Arc<SignalCli>) -> Self {
-        let tools = vec![build_list_conversations_tool(), build_send_message_tool()];
+    fn new(accounts: Vec<Arc<AccountHandler>>, settings: Settings, logs: McpLogForwarder) -> Self {
+        let mut tools = vec![
+            build_list_conversations_tool(),
+            build_send_message_tool(),
+            build_receive_messages_tool(),
+            build_wait_for_message_tool(),
+        ];
+        tools.extend(build_contact_tools());
+        tools.extend(build_group_tools());
+        tools.extend(build_identity_tools());
+        tools.extend(build_device_tools());
+        tools.push(build_export_tool());
+        tools.push(build_purge_tool());
+        tools.push(build_health_tool());
+        for tool in &mut tools {
+            add_account_property(tool);
+        }
         let resources = build_resource_entries();
         Self {
-            signal_cli,
+            accounts,
+            settings,
             tools,
             resources,
+            prompts: build_prompts(),
+            logs,
+            in_flight: InFlightCalls::default(),
+        }
+    }
+
+    /// Route a tool call to the handler for its tool and account.
+    async fn dispatch_tool(
+        &self,
+        params: CallToolRequestParams,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let account = parse_arguments::<AccountArg>(params.arguments.clone())?.account;
+        if params.name == HEALTH_TOOL {
+            return self.invoke_health(account.as_deref()).await;
+        }
+        if params.name == EXPORT_CONVERSATION_TOOL {
+            return self
+                .invoke_export(account.as_deref(), params.arguments)
+                .await;
+        }
+        if params.name == WAIT_FOR_MESSAGE_TOOL {
+            return self
+                .invoke_wait_for_message(account.as_deref(), params.arguments, runtime)
+                .await;
+        }
+
+        let handler = self.resolve_account(account.as_deref())?;
+        handler.invoke_tool(&params.name, params.arguments).await
+    }
+
+    /// Find the account named by alias or number, or the default when none is given.
+    fn resolve_account(
+        &self,
+        requested: Option<&str>,
+    ) -> std::result::Result<&AccountHandler, CallToolError> {
+        let found = match requested.map(str::trim).filter(|name| !name.is_empty()) {
+            Some(name) => self
+                .accounts
+                .iter()
+                .find(|account| account.alias == name || account.number == name),
+            None => self.accounts.first(),
+        };
+        found.map(|account| account.as_ref()).ok_or_else(|| {
+            let known: Vec<&str> = self
+                .accounts
+                .iter()
+                .map(|account| account.alias.as_str())
+                .collect();
+            CallToolError::from_message(format!(
+                "unknown account {:?}; configured accounts: {}",
+                requested.unwrap_or_default(),
+                known.join(", ")
+            ))
+        })
+    }
+}
+
+impl AccountHandler {
+    async fn invoke_tool(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        match name {
+            LIST_CONVERSATIONS_TOOL => {
+                let args = parse_arguments::<ListConversationsArgs>(arguments)?;
+                self.invoke_list_conversations(args).await
+            }
+            SEND_MESSAGE_TOOL => {
+                let args = parse_arguments::<SendMessageArgs>(arguments)?;
+                self.invoke_send_message(args).await
+            }
+            RECEIVE_MESSAGES_TOOL => {
+                let args = parse_arguments::<ReceiveMessagesArgs>(arguments)?;
+                self.invoke_receive_messages(args).await
+            }
+            tool if CONTACT_TOOLS.contains(&tool) => {
+                self.invoke_contact_tool(tool, arguments).await
+            }
+            tool if IDENTITY_TOOLS.contains(&tool) => {
+                self.invoke_identity_tool(tool, arguments).await
+            }
+            tool if GROUP_TOOLS.contains(&tool) => self.invoke_group_tool(tool, arguments).await,
+            tool if DEVICE_TOOLS.contains(&tool) => self.invoke_device_tool(tool, arguments).await,
+            PURGE_CONVERSATION_TOOL => self.invoke_purge_conversation(arguments).await,
+            _ => Err(CallToolError::unknown_tool(name.to_string())),
         }
     }
 
     async fn invoke_list_conversations(
         &self,
+        args: ListConversationsArgs,
     ) -> std::result::Result<CallToolResult, CallToolError> {
         match self.signal_cli.list_chats().await {
             Ok(chats) => {
+                let chats: Vec<_> = chats
+                    .into_iter()
+                    .filter(|chat| args.include_blocked || !chat.blocked)
+                    .collect();
                 if chats.is_empty() {
                     let content =
                         TextContent::new("No Signal conversations found.".to_string(), None, None);
@@ -134,7 +421,8 @@ impl SignalMcpHandler {
                     let mut lines = Vec::with_capacity(chats.len());
                     for chat in chats {
                         let label = chat.name.as_deref().unwrap_or("<unnamed>");
-                        lines.push(format!("{} — {}", chat.id, label));
+                        let blocked = if chat.blocked { " [blocked]" } else { "" };
+                        lines.push(format!("{} — {}{}", chat.id, label, blocked));
                     }
                     let body = lines.join("\n");
                     let content = TextContent::new(body, None, None);
@@ -161,11 +449,59 @@ impl SignalMcpHandler {
             ));
         }
 
-        match self
+        let (text, styles) = match args.format {
+            MessageFormat::Markdown => parse_markdown(&args.message),
+            MessageFormat::Plain => (args.message.clone(), Vec::new()),
+        };
+        let mentions = self
+            .resolve_mentions(&args.recipient, &text, &args.mentions)
+            .await?;
+
+        let mut warnings = Vec::new();
+        let mut result = self
             .signal_cli
-            .send_message(&args.recipient, &args.message)
-            .await
+            .send_message(&args.recipient, &text, &mentions, &styles)
+            .await;
+        if let Some(SignalCliError::UntrustedIdentity { addresses, .. }) = result
+            .as_ref()
+            .err()
+            .and_then(|err| err.downcast_ref::<SignalCliError>())
         {
+            let group = is_group_recipient(&args.recipient);
+            let mut addresses = addresses.clone();
+            if addresses.is_empty() {
+                if !group {
+                    addresses.push(args.recipient.clone());
+                } else {
+                    // Without names there is nobody to trust or report, and no
+                    // telling which members were skipped.
+                    warn!(recipient = %args.recipient, "untrusted identity in group send without an address");
+                    return Err(CallToolError::from_message(format!(
+                        "the safety number of at least one member of {} changed, but signal-cli did not say whose, so the affected members could not be identified and nobody was trusted. The message may not have reached every member. Look for untrusted entries with `{}` and confirm them with `{}` before messaging the group again.",
+                        args.recipient, LIST_IDENTITIES_TOOL, TRUST_IDENTITY_TOOL
+                    )));
+                }
+            }
+            warnings = self.handle_untrusted_identities(&addresses, group).await?;
+            if group {
+                // Every trusted member already has the message; resending
+                // to the group would deliver it to them a second time.
+                let mut lines = vec![format!(
+                    "Message delivered to {} except {}, whose safety number changed; it was not resent",
+                    args.recipient,
+                    addresses.join(", ")
+                )];
+                lines.extend(warnings);
+                let content = TextContent::new(lines.join("\n"), None, None);
+                return Ok(CallToolResult::text_content(vec![content]));
+            }
+            result = self
+                .signal_cli
+                .send_message(&args.recipient, &text, &mentions, &styles)
+                .await;
+        }
+
+        match result {
             Ok(receipt) => {
                 let mut lines = vec![
                     format!("Message delivered to {}", args.recipient),
@@ -174,6 +510,7 @@ impl SignalMcpHandler {
                 if receipt.is_empty() {
                     lines.push("No response payload from signal-cli".to_string());
                 }
+                lines.extend(warnings);
                 let content = TextContent::new(lines.join("\n"), None, None);
                 Ok(CallToolResult::text_content(vec![content]))
             }
@@ -186,10 +523,225 @@ impl SignalMcpHandler {
             }
         }
     }
+
+    /// Apply the identity policy to contacts whose safety number changed.
+    ///
+    /// Under `warn` the new keys are trusted and a warning per contact is returned so
+    /// the caller can retry; other policies record the change and refuse the send.
+    /// For a `group` send the other members already received the message.
+    async fn handle_untrusted_identities(
+        &self,
+        addresses: &[String],
+        group: bool,
+    ) -> std::result::Result<Vec<String>, CallToolError> {
+        let auto_trust =
+            self.policy.borrow().identity_policy == IdentityPolicy::Warn && !addresses.is_empty();
+        let action = if auto_trust {
+            IdentityAction::AutoTrusted
+        } else {
+            IdentityAction::SendBlocked
+        };
+
+        let mut warnings = Vec::new();
+        for address in addresses {
+            if auto_trust {
+                self.signal_cli
+                    .trust_identity(address, None)
+                    .await
+                    .map_err(|err| signal_cli_error("trust", err))?;
+                warn!(%address, "safety number changed; trusted automatically per policy");
+                warnings.push(format!(
+                    "Warning: the safety number for {} changed and was trusted automatically",
+                    address
+                ));
+            }
+            self.record_identity_change(address, action).await;
+        }
+
+        if auto_trust {
+            Ok(warnings)
+        } else {
+            let who = if addresses.is_empty() {
+                "a recipient".to_string()
+            } else {
+                addresses.join(", ")
+            };
+            let (outcome, next) = if group {
+                (
+                    format!(
+                        "message not delivered to {} (the group's other members received it)",
+                        who
+                    ),
+                    "messaging them again",
+                )
+            } else {
+                ("message not sent".to_string(), "retrying")
+            };
+            Err(CallToolError::from_message(format!(
+                "{}: the safety number for {} is not trusted. Compare it with `{}` and confirm with `{}` before {}.",
+                outcome, who, LIST_IDENTITIES_TOOL, TRUST_IDENTITY_TOOL, next
+            )))
+        }
+    }
+
+    async fn record_identity_change(&self, address: &str, action: IdentityAction) {
+        let event = StoredEvent::IdentityChange(IdentityChange {
+            address: address.to_string(),
+            detected_at: Utc::now(),
+            action,
+        });
+        if let Err(err) = self
+            .store
+            .append(&ConversationId(address.to_string()), &event)
+            .await
+        {
+            warn!(?err, %address, "failed to record identity change");
+        }
+    }
+
+    /// Combine explicit mention spans with `@Name` tokens resolved against known contacts.
+    ///
+    /// Offsets refer to `text` after any Markdown conversion.
+    async fn resolve_mentions(
+        &self,
+        recipient: &str,
+        text: &str,
+        explicit: &[MentionSpan],
+    ) -> std::result::Result<Vec<MentionSpan>, CallToolError> {
+        let text_len = utf16_len(text);
+        if let Some(span) = explicit
+            .iter()
+            .find(|span| span.length == 0 || span.start.saturating_add(span.length) > text_len)
+        {
+            return Err(CallToolError::from_message(format!(
+                "mention {}:{} is outside the message text ({} UTF-16 units)",
+                span.start, span.length, text_len
+            )));
+        }
+
+        if !is_group_recipient(recipient) || !text.contains('@') {
+            return Ok(explicit.to_vec());
+        }
+
+        let chats = self.signal_cli.list_chats().await.map_err(|err| {
+            warn!(?err, "signal-cli listChats failed while resolving mentions");
+            CallToolError::from_message(format!("failed to resolve mentions: {}", err))
+        })?;
+        let contacts: Vec<(String, String)> = chats
+            .into_iter()
+            .filter(|chat| chat.kind == ChatKind::Contact)
+            .filter_map(|chat| chat.name.map(|name| (chat.id, name)))
+            .collect();
+
+        Ok(merge_mentions(
+            explicit,
+            find_named_mentions(text, &contacts),
+        ))
+    }
+
+    async fn invoke_receive_messages(
+        &self,
+        args: ReceiveMessagesArgs,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let timeout = args
+            .timeout_seconds
+            .unwrap_or(DEFAULT_RECEIVE_TIMEOUT_SECONDS);
+        let messages = self.receive_and_store(timeout).await?;
+
+        let body = if messages.is_empty() {
+            "No new Signal messages.".to_string()
+        } else {
+            messages
+                .iter()
+                .map(format_message)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let content = TextContent::new(body, None, None);
+        Ok(CallToolResult::text_content(vec![content]))
+    }
+
+    /// Receive pending messages and store them.
+    ///
+    /// signal-cli acknowledges messages to the Signal servers as it receives
+    /// them, so this runs to completion even if the tool call is cancelled.
+    async fn receive_and_store(
+        &self,
+        timeout: u64,
+    ) -> std::result::Result<Vec<Message>, CallToolError> {
+        let signal_cli = self.signal_cli.clone();
+        let store = self.store.clone();
+        shielded(async move { receive_into_store(&signal_cli, &store, timeout).await })
+            .await
+            .map_err(CallToolError::from_message)
+    }
+}
+
+async fn receive_into_store(
+    signal_cli: &SignalCli,
+    store: &Store,
+    timeout: u64,
+) -> std::result::Result<Vec<Message>, String> {
+    let envelopes = signal_cli.receive(timeout).await.map_err(|err| {
+        warn!(?err, "signal-cli receive failed from tool invocation");
+        format!("signal-cli receive failed: {}", err)
+    })?;
+
+    // A stale directory only degrades display names, so tolerate listing failures.
+    let directory: HashMap<String, String> = match signal_cli.list_chats().await {
+        Ok(chats) => chats
+            .into_iter()
+            .filter_map(|chat| chat.name.map(|name| (chat.id, name)))
+            .collect(),
+        Err(err) => {
+            warn!(?err, "signal-cli listChats failed while resolving senders");
+            HashMap::new()
+        }
+    };
+
+    let messages: Vec<Message> = envelopes
+        .iter()
+        .filter_map(|envelope| parse_envelope(envelope, &directory))
+        .collect();
+
+    // Identity changes are logged per conversation, and they only mean
+    // something next to the messages around them, so the conversation log
+    // keeps what was received too. It is plaintext unless `[encryption]` is set.
+    let now = Utc::now();
+    for message in &messages {
+        // Messages that already disappeared while we were offline are shown once, never kept.
+        if message
+            .expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            continue;
+        }
+        let event = StoredEvent::Message(message.clone());
+        if let Err(err) = store.append(&message.conversation_id, &event).await {
+            warn!(?err, id = %message.id.0, "failed to store received message");
+        }
+    }
+    Ok(messages)
 }
 
 #[async_trait]
 impl ServerHandler for SignalMcpHandler {
+    async fn on_initialized(&self, runtime: Arc<dyn McpServer>) {
+        self.logs.attach(runtime);
+    }
+
+    async fn handle_set_level_request(
+        &self,
+        request: SetLevelRequest,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<rust_mcp_sdk::schema::Result, rust_mcp_sdk::schema::RpcError> {
+        let method = SetLevelRequest::method_name();
+        runtime.assert_server_request_capabilities(&method)?;
+
+        self.logs.set_level(runtime, request.params.level);
+        Ok(rust_mcp_sdk::schema::Result::default())
+    }
+
     async fn handle_list_tools_request(
         &self,
         _request: ListToolsRequest,
@@ -213,11 +765,18 @@ impl ServerHandler for SignalMcpHandler {
         let method = ListResourcesRequest::method_name();
         runtime.assert_server_request_capabilities(&method)?;
 
-        let resources = self
+        let mut resources: Vec<Resource> = self
             .resources
             .iter()
             .map(|entry| entry.descriptor.clone())
             .collect();
+        match list_exports(&self.settings).await {
+            Ok(names) => resources.extend(names.iter().filter_map(|name| {
+                let format = name.rsplit('.').next()?.parse().ok()?;
+                Some(export_resource(name, format))
+            })),
+            Err(err) => warn!(?err, "failed to list conversation exports"),
+        }
 
         Ok(ListResourcesResult {
             resources,
@@ -226,9 +785,63 @@ impl ServerHandler for SignalMcpHandler {
         })
     }
 
+    async fn handle_list_resource_templates_request(
+        &self,
+        _request: ListResourceTemplatesRequest,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<ListResourceTemplatesResult, rust_mcp_sdk::schema::RpcError> {
+        let method = ListResourceTemplatesRequest::method_name();
+        runtime.assert_server_request_capabilities(&method)?;
+
+        Ok(ListResourceTemplatesResult {
+            resource_templates: vec![conversation_resource_template()],
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
+    async fn handle_list_prompts_request(
+        &self,
+        _request: ListPromptsRequest,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<ListPromptsResult, rust_mcp_sdk::schema::RpcError> {
+        let method = ListPromptsRequest::method_name();
+        runtime.assert_server_request_capabilities(&method)?;
+
+        Ok(ListPromptsResult {
+            prompts: self.prompts.clone(),
+            meta: None,
+            next_cursor: None,
+        })
+    }
+
+    async fn handle_get_prompt_request(
+        &self,
+        request: GetPromptRequest,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<GetPromptResult, rust_mcp_sdk::schema::RpcError> {
+        let method = GetPromptRequest::method_name();
+        runtime.assert_server_request_capabilities(&method)?;
+
+        let params = request.params;
+        self.get_prompt(&params.name, params.arguments.unwrap_or_default())
+            .await
+    }
+
+    async fn handle_complete_request(
+        &self,
+        request: CompleteRequest,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<CompleteResult, rust_mcp_sdk::schema::RpcError> {
+        let method = CompleteRequest::method_name();
+        runtime.assert_server_request_capabilities(&method)?;
+
+        self.complete(request.params).await
+    }
+
     async fn handle_call_tool_request(
         &self,
-        request: CallToolRequest,
+        mut request: CallToolRequest,
         runtime: Arc<dyn McpServer>,
     ) -> std::result::Result<CallToolResult, CallToolError> {
         let method = CallToolRequest::method_name();
@@ -236,16 +849,29 @@ impl ServerHandler for SignalMcpHandler {
             .assert_server_request_capabilities(&method)
             .map_err(CallToolError::new)?;
 
-        let params = request.params;
-        let name = params.name;
-        match name.as_str() {
-            LIST_CONVERSATIONS_TOOL => self.invoke_list_conversations().await,
-            SEND_MESSAGE_TOOL => {
-                let args = parse_arguments::<SendMessageArgs>(params.arguments)?;
-                self.invoke_send_message(args).await
-            }
-            _ => Err(CallToolError::unknown_tool(name)),
+        // Only the stdio transport supplies request IDs; never trust one from a client.
+        let request_id = take_request_id(&mut request.params)
+            .filter(|_| self.settings.transport == TransportKind::Stdio);
+        let name = request.params.name.clone();
+        let mut call = self.in_flight.start(runtime.clone(), &name, request_id);
+        tokio::select! {
+            result = call.scope(self.dispatch_tool(request.params, runtime)) => return result,
+            () = call.cancelled() => {}
         }
+        call.settle().await;
+        Err(CallToolError::from_message(format!(
+            "{} was cancelled by the client",
+            name
+        )))
+    }
+
+    async fn handle_cancelled_notification(
+        &self,
+        notification: CancelledNotification,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<(), rust_mcp_sdk::schema::RpcError> {
+        self.in_flight.cancel(&runtime, &notification.params);
+        Ok(())
     }
 
     async fn handle_read_resource_request(
@@ -269,6 +895,26 @@ impl ServerHandler for SignalMcpHandler {
                 uri: uri.clone(),
             };
 
+            Ok(ReadResourceResult {
+                contents: vec![contents.into()],
+                meta: None,
+            })
+        } else if let Some(name) = uri.strip_prefix(CONVERSATION_URI_PREFIX) {
+            let contents = self.read_conversation_resource(name).await?;
+            Ok(ReadResourceResult {
+                contents: vec![contents.into()],
+                meta: None,
+            })
+        } else if let Some(name) = uri.strip_prefix(EXPORT_URI_PREFIX) {
+            let (text, format) = read_export(&self.settings, name).await.map_err(|err| {
+                rust_mcp_sdk::schema::RpcError::invalid_params().with_message(format!("{:#}", err))
+            })?;
+            let contents = TextResourceContents {
+                meta: None,
+                mime_type: Some(format.mime_type().to_string()),
+                text,
+                uri: uri.clone(),
+            };
             Ok(ReadResourceResult {
                 contents: vec![contents.into()],
                 meta: None,
@@ -285,12 +931,21 @@ fn build_list_conversations_tool() -> Tool {
     annotations.read_only_hint = Some(true);
     annotations.destructive_hint = Some(false);
 
-    let input_schema = ToolInputSchema::new(Vec::new(), None);
+    let mut properties: HashMap<String, Map<String, Value>> = HashMap::new();
+    properties.insert(
+        "include_blocked".to_string(),
+        schema_property(
+            "boolean",
+            "Include blocked contacts and groups (default false)",
+        ),
+    );
+
+    let input_schema = ToolInputSchema::new(Vec::new(), Some(properties));
 
     Tool {
         annotations: Some(annotations),
         description: Some(
-            "Return known Signal contacts and groups using signal-cli listContacts/listGroups."
+            "Return known Signal contacts and groups using signal-cli listContacts/listGroups. Blocked entries are hidden unless requested."
                 .into(),
         ),
         input_schema,
@@ -320,10 +975,71 @@ fn build_send_message_tool() -> Tool {
     message_schema.insert("type".to_string(), Value::String("string".into()));
     message_schema.insert(
         "description".to_string(),
-        Value::String("Message body to send".into()),
+        Value::String(
+            "Message body to send. Markdown (**bold**, *italic*, ~~strike~~, ||spoiler||, `code`) becomes Signal text styles unless `format` is `plain`."
+                .into(),
+        ),
     );
     properties.insert("message".to_string(), message_schema);
 
+    let mut format_schema = Map::new();
+    format_schema.insert("type".to_string(), Value::String("string".into()));
+    format_schema.insert(
+        "enum".to_string(),
+        Value::Array(vec![
+            Value::String("markdown".into()),
+            Value::String("plain".into()),
+        ]),
+    );
+    format_schema.insert(
+        "description".to_string(),
+        Value::String("How to interpret `message` (default markdown)".into()),
+    );
+    properties.insert("format".to_string(), format_schema);
+
+    let mut mention_properties = Map::new();
+    for (field, kind, description) in [
+        (
+            "start",
+            "integer",
+            "Offset of the mention in UTF-16 code units",
+        ),
+        (
+            "length",
+            "integer",
+            "Length of the mention in UTF-16 code units",
+        ),
+        ("recipient", "string", "Mentioned member's number or UUID"),
+    ] {
+        let mut field_schema = Map::new();
+        field_schema.insert("type".to_string(), Value::String(kind.into()));
+        field_schema.insert("description".to_string(), Value::String(description.into()));
+        mention_properties.insert(field.to_string(), Value::Object(field_schema));
+    }
+    let mut mention_item = Map::new();
+    mention_item.insert("type".to_string(), Value::String("object".into()));
+    mention_item.insert("properties".to_string(), Value::Object(mention_properties));
+    mention_item.insert(
+        "required".to_string(),
+        Value::Array(vec![
+            Value::String("start".into()),
+            Value::String("length".into()),
+            Value::String("recipient".into()),
+        ]),
+    );
+
+    let mut mentions_schema = Map::new();
+    mentions_schema.insert("type".to_string(), Value::String("array".into()));
+    mentions_schema.insert("items".to_string(), Value::Object(mention_item));
+    mentions_schema.insert(
+        "description".to_string(),
+        Value::String(
+            "Explicit mention spans for group messages, offset into the text after Markdown conversion. `@Name` tokens matching a contact are mentioned automatically."
+                .into(),
+        ),
+    );
+    properties.insert("mentions".to_string(), mentions_schema);
+
     let input_schema = ToolInputSchema::new(
         vec!["recipient".to_string(), "message".to_string()],
         Some(properties),
@@ -340,6 +1056,154 @@ fn build_send_message_tool() -> Tool {
     }
 }
 
+fn build_receive_messages_tool() -> Tool {
+    let mut annotations = ToolAnnotations::default();
+    annotations.read_only_hint = Some(false);
+    annotations.destructive_hint = Some(false);
+
+    let mut properties: HashMap<String, Map<String, Value>> = HashMap::new();
+
+    let mut timeout_schema = Map::new();
+    timeout_schema.insert("type".to_string(), Value::String("integer".into()));
+    timeout_schema.insert(
+        "description".to_string(),
+        Value::String("Seconds to wait for new messages (default 1)".into()),
+    );
+    properties.insert("timeout_seconds".to_string(), timeout_schema);
+
+    let input_schema = ToolInputSchema::new(Vec::new(), Some(properties));
+
+    Tool {
+        annotations: Some(annotations),
+        description: Some(
+            "Fetch pending Signal messages using signal-cli receive. Messages are consumed from the queue."
+                .into(),
+        ),
+        input_schema,
+        meta: None,
+        name: RECEIVE_MESSAGES_TOOL.to_string(),
+        output_schema: None,
+        title: Some("Receive Signal Messages".into()),
+    }
+}
+
+fn format_message(message: &Message) -> String {
+    let author = match &message.author.display_name {
+        Some(name) => format!("{} ({})", name, message.author.address),
+        None => message.author.address.clone(),
+    };
+    let body = match &message.body {
+        MessageBody::Text {
+            text,
+            mentions,
+            styles,
+        } => render_markdown(text, mentions, styles),
+        MessageBody::Sticker { .. } => "[sticker]".to_string(),
+        MessageBody::Unknown => "[unsupported message]".to_string(),
+    };
+
+    let mut line = format!(
+        "[{}] {} in {}: {}",
+        message.timestamp.to_rfc3339(),
+        author,
+        message.conversation_id.0,
+        body
+    );
+    if !message.attachments.is_empty() {
+        line.push_str(&format!(" ({} attachment(s))", message.attachments.len()));
+    }
+    if let Some(seconds) = message.expires_in_seconds {
+        line.push_str(&format!(" (disappears after {}s)", seconds));
+    }
+    line
+}
+
+/// Whether `author` names the sender of `message` by address or (case-insensitive) display name.
+fn sent_by(message: &Message, author: &str) -> bool {
+    message.author.address == author
+        || message
+            .author
+            .display_name
+            .as_deref()
+            .is_some_and(|name| name.eq_ignore_ascii_case(author))
+}
+
+fn tool_annotations(read_only: bool, destructive: bool) -> ToolAnnotations {
+    ToolAnnotations {
+        read_only_hint: Some(read_only),
+        destructive_hint: Some(destructive),
+        ..ToolAnnotations::default()
+    }
+}
+
+fn build_tool(
+    name: &str,
+    title: &str,
+    description: &str,
+    required: &[&str],
+    properties: Vec<(&str, Map<String, Value>)>,
+    annotations: ToolAnnotations,
+) -> Tool {
+    let properties: HashMap<String, Map<String, Value>> = properties
+        .into_iter()
+        .map(|(key, schema)| (key.to_string(), schema))
+        .collect();
+    let required = required.iter().map(|field| field.to_string()).collect();
+
+    Tool {
+        annotations: Some(annotations),
+        description: Some(description.into()),
+        input_schema: ToolInputSchema::new(required, Some(properties)),
+        meta: None,
+        name: name.to_string(),
+        output_schema: None,
+        title: Some(title.into()),
+    }
+}
+
+/// Every tool accepts `account`, so it is added to the schemas in one place.
+fn add_account_property(tool: &mut Tool) {
+    tool.input_schema
+        .properties
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            "account".to_string(),
+            schema_property(
+                "string",
+                "Account alias or number to act as (default: the first configured account)",
+            ),
+        );
+}
+
+fn schema_property(kind: &str, description: &str) -> Map<String, Value> {
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), Value::String(kind.into()));
+    schema.insert("description".to_string(), Value::String(description.into()));
+    schema
+}
+
+fn string_list_property(description: &str) -> Map<String, Value> {
+    let mut items = Map::new();
+    items.insert("type".to_string(), Value::String("string".into()));
+
+    let mut schema = schema_property("array", description);
+    schema.insert("items".to_string(), Value::Object(items));
+    schema
+}
+
+fn text_result(body: String) -> CallToolResult {
+    CallToolResult::text_content(vec![TextContent::new(body, None, None)])
+}
+
+/// Log a failed signal-cli operation and turn it into a tool error.
+fn signal_cli_error(operation: &str, err: anyhow::Error) -> CallToolError {
+    warn!(
+        ?err,
+        operation, "signal-cli operation failed from tool invocation"
+    );
+    CallToolError::from_message(format!("{:#}", err))
+}
+
 fn parse_arguments<T>(
     arguments: Option<Map<String, Value>>,
 ) -> std::result::Result<T, CallToolError>
@@ -359,8 +1223,22 @@ This MCP server bridges a linked Signal account via `signal-cli`.
 
 ## Current Tools
 
-- `signal_list_conversations` — lists known contacts and group chats using `signal-cli listContacts`/`listGroups`.
-- `signal_send_message` — sends a text message to a phone number or group ID via `signal-cli send`.
+- `signal_list_conversations` — lists known contacts and group chats using `signal-cli listContacts`/`listGroups`; pass `include_blocked` to show blocked entries.
+- `signal_update_contact`, `signal_remove_contact`, `signal_block`, `signal_unblock` — maintain the address book, nicknames and notes.
+- `signal_send_message` — sends a text message to a phone number or group ID via `signal-cli send`. In groups, `@Name` tokens matching a contact (or explicit `mentions` spans) become Signal mentions, and Markdown (`**bold**`, `*italic*`, `~~strike~~`, `||spoiler||`, `` `code` ``) becomes Signal text styles.
+- `signal_get_group` — returns a group's roster, admins, description, invite link and permissions via `signal-cli listGroups -d`.
+- `signal_create_group`, `signal_update_group_details`, `signal_add_group_members`, `signal_remove_group_members`, `signal_set_group_admins`, `signal_reset_group_invite_link`, `signal_leave_group` — manage groups via `signal-cli updateGroup`/`quitGroup`.
+- `signal_receive_messages` — fetches pending messages via `signal-cli receive`, rendering mentions as `@Name` and text styles as Markdown. Received messages are appended to the local store under `storage`.
+- `signal_wait_for_message` — blocks until a message matching a conversation, author and/or text pattern arrives (default 60s, max 600s), storing and listing anything else received meanwhile; pass `progress_token` to get `notifications/progress` updates.
+- `signal_list_identities`, `signal_trust_identity` — inspect safety numbers and trust a contact's new identity key.
+- `signal_list_devices`, `signal_add_device`, `signal_remove_device` — audit, link and unlink devices on the account via `signal-cli listDevices`/`addDevice`/`removeDevice`.
+- `signal_health` — pass/fail report on the signal-cli executable and version, account registration and storage, daemon connectivity and `storage` writability. `signal-mcp-server check` prints the same report.
+
+Prompts `signal_catch_up`, `signal_draft_reply` and `signal_summarize_unread` embed stored transcripts, which are also readable as `resource://signal/conversations/<number>/<conversation>`. Prompt arguments and the template's variables support `completion/complete` against contact and group names, numbers and IDs.
+
+Over stdio, cancelling a tool call (`notifications/cancelled`) by its request ID kills its signal-cli list process and drops a send not yet handed to signal-cli; sends already running finish, and received messages are stored before the cancellation is reported. Cancellations over HTTP are ignored.
+
+Server logs reach the client as `notifications/message` (`warning` and above until `logging/setLevel`; `debug` includes signal-cli stderr). `[logging]` sends them to stderr (default), rotating files under `storage/logs` and/or journald, as text or JSON, with `level` and per-module `modules` filters; stdout is never used.
 
 ## Configuration
 
@@ -370,15 +1248,31 @@ Provide a `config.toml` (or `SIGNAL_MCP__*` environment variables) with:
 account = "+1XXXXXXXXXX"
 signal_cli_path = "/path/to/signal-cli"
 storage = "./var"
+
+[[accounts]]
+alias = "alerts"
+number = "+1YYYYYYYYYY"
 ```
 
+Every tool takes an optional `account` argument (alias or number); without it the top-level `account` (alias `default`) is used. Each account keeps its own conversation store under `storage/accounts/`.
+
+With `[encryption]` `key_file` or `passphrase_file` set, store records are encrypted at rest; the server refuses to start if the key is missing or wrong for an encrypted store.
+
+`[retention]` `max_age_days` and `max_messages` (with per-conversation overrides under `[[retention.conversations]]`) are enforced every `interval_minutes`; `signal_purge_conversation` removes a conversation's stored history and attachments on demand. Disappearing messages are deleted from the store when their timer elapses.
+
+`signal_export_conversation` renders stored messages as Markdown, JSON Lines, HTML or CSV under `storage/exports/`; exports are listed as `resource://signal/exports/...` resources. With encryption, retention limits or disappearing messages the export is returned as text only and never written to disk.
+
+`[timeouts]` `default_seconds` (60) and per-command `operations` limits kill signal-cli invocations that hang; tool calls then fail with a timeout error, which names lock contention when another signal-cli instance holds the account.
+
 The Signal account must already be linked or registered using `signal-cli`.
 
+`identity_policy` controls sends after a contact's safety number changes: `trust_on_first_use` (default) refuses them until the key is trusted, `block` also requires explicit trust for first contact, and `warn` trusts the new key automatically and reports it. Every change is recorded in the local store.
+
 ## Roadmap Highlights
 
 - Fetch and normalize conversation/message history.
 - Stream live events from `signal-cli jsonRpc` and expose MCP notifications.
-- Attachment handling, search, health checks, and richer telemetry.
+- Attachment handling, search, and richer telemetry.
 "#;
 
     let descriptor = Resource {
@@ -0,0 +1,327 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rust_mcp_sdk::schema::schema_utils::{
+    ClientMessage, ClientMessages, MessageFromServer, RequestFromClient, ServerMessage,
+    ServerMessages,
+};
+use rust_mcp_sdk::schema::{
+    CallToolRequestParams, CancelledNotificationParams, ClientRequest, RequestId,
+};
+use rust_mcp_sdk::{
+    IoStream, McpDispatch, McpServer, MessageDispatcher, SessionId, StdioTransport, Transport,
+    TransportDispatcher, TransportResult,
+};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::info;
+
+/// Argument [`RequestIdTransport`] adds to every `tools/call` to carry its JSON-RPC ID.
+const REQUEST_ID_ARGUMENT: &str = "_jsonrpc_request_id";
+
+tokio::task_local! {
+    /// Held for reading by [`shielded`] work of the tool call running on this task.
+    static SHIELD: Arc<RwLock<()>>;
+}
+
+/// Tool calls currently running, so `notifications/cancelled` can abort them.
+///
+/// The SDK does not hand request IDs to tool handlers, so over stdio
+/// [`RequestIdTransport`] passes each call's ID along in its arguments. A
+/// cancellation aborts only the call recorded under its `requestId`; calls
+/// started without one (over HTTP) run to completion.
+#[derive(Default)]
+pub(super) struct InFlightCalls {
+    calls: Mutex<Vec<InFlightCall>>,
+    next_id: AtomicU64,
+}
+
+struct InFlightCall {
+    id: u64,
+    runtime: Arc<dyn McpServer>,
+    request_id: Value,
+    tool: String,
+    cancel: oneshot::Sender<()>,
+}
+
+/// Registration of one running call, removed again when dropped.
+pub(super) struct CallGuard<'a> {
+    calls: &'a InFlightCalls,
+    id: u64,
+    cancelled: Option<oneshot::Receiver<()>>,
+    shield: Arc<RwLock<()>>,
+}
+
+impl InFlightCalls {
+    /// Record a call that can be cancelled by `request_id`, when it is known.
+    pub(super) fn start(
+        &self,
+        runtime: Arc<dyn McpServer>,
+        tool: &str,
+        request_id: Option<Value>,
+    ) -> CallGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancelled = request_id.map(|request_id| {
+            let (cancel, cancelled) = oneshot::channel();
+            self.calls
+                .lock()
+                .expect("in-flight call lock poisoned")
+                .push(InFlightCall {
+                    id,
+                    runtime,
+                    request_id,
+                    tool: tool.to_string(),
+                    cancel,
+                });
+            cancelled
+        });
+        CallGuard {
+            calls: self,
+            id,
+            cancelled,
+            shield: Arc::default(),
+        }
+    }
+
+    /// Abort the client's running call named by a `notifications/cancelled`.
+    pub(super) fn cancel(
+        &self,
+        runtime: &Arc<dyn McpServer>,
+        params: &CancelledNotificationParams,
+    ) {
+        let request_id = request_id_value(&params.request_id);
+        let reason = params.reason.as_deref().unwrap_or("none given");
+        let mut calls = self.calls.lock().expect("in-flight call lock poisoned");
+        let position = calls
+            .iter()
+            .position(|call| Arc::ptr_eq(&call.runtime, runtime) && call.request_id == request_id);
+        match position {
+            Some(index) => {
+                let call = calls.swap_remove(index);
+                info!(%request_id, tool = %call.tool, reason, "cancelling tool call");
+                let _ = call.cancel.send(());
+            }
+            None => info!(
+                %request_id,
+                reason,
+                "cancellation for a request that is not a running, cancellable tool call"
+            ),
+        }
+    }
+}
+
+impl CallGuard<'_> {
+    /// Run `call` so that [`shielded`] work inside it is tracked by this guard.
+    pub(super) fn scope<F: Future>(&self, call: F) -> impl Future<Output = F::Output> {
+        SHIELD.scope(self.shield.clone(), call)
+    }
+
+    /// Resolves once the call has been cancelled; never for calls without a request ID.
+    pub(super) async fn cancelled(&mut self) {
+        let cancelled = match self.cancelled.as_mut() {
+            Some(cancelled) => cancelled.await.is_ok(),
+            None => false,
+        };
+        if !cancelled {
+            // Not cancellable, or the registration is gone; never resolve.
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Wait for shielded work the cancelled call started to finish.
+    pub(super) async fn settle(&self) {
+        let _ = self.shield.write().await;
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        self.calls
+            .calls
+            .lock()
+            .expect("in-flight call lock poisoned")
+            .retain(|call| call.id != self.id);
+    }
+}
+
+/// Run `work` to completion even if the tool call awaiting it is cancelled.
+///
+/// For steps that cannot be safely interrupted, such as storing messages
+/// signal-cli has already acknowledged. A cancelled call reports back only
+/// once its shielded work is done.
+pub(super) async fn shielded<F>(work: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let guard = match SHIELD.try_with(Arc::clone) {
+        Ok(shield) => Some(shield.read_owned().await),
+        Err(_) => None,
+    };
+    let task = tokio::spawn(async move {
+        let _guard = guard;
+        work.await
+    });
+    match task.await {
+        Ok(output) => output,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+/// Remove the request ID [`RequestIdTransport`] added to `params`.
+pub(super) fn take_request_id(params: &mut CallToolRequestParams) -> Option<Value> {
+    params.arguments.as_mut()?.remove(REQUEST_ID_ARGUMENT)
+}
+
+fn request_id_value(request_id: &RequestId) -> Value {
+    serde_json::to_value(request_id).unwrap_or(Value::Null)
+}
+
+/// Stdio transport that copies each `tools/call` request's JSON-RPC ID into its arguments.
+pub(super) struct RequestIdTransport {
+    inner: StdioTransport<ClientMessage>,
+}
+
+impl RequestIdTransport {
+    pub(super) fn new(inner: StdioTransport<ClientMessage>) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped transport, as the server side uses it.
+    fn inner(
+        &self,
+    ) -> &impl Transport<ClientMessages, MessageFromServer, ClientMessage, ServerMessages, ServerMessage>
+    {
+        &self.inner
+    }
+}
+
+fn tag_message(message: &mut ClientMessage) {
+    if let ClientMessage::Request(request) = message {
+        if let RequestFromClient::ClientRequest(ClientRequest::CallToolRequest(call)) =
+            &mut request.request
+        {
+            call.params
+                .arguments
+                .get_or_insert_with(Default::default)
+                .insert(
+                    REQUEST_ID_ARGUMENT.to_string(),
+                    request_id_value(&request.id),
+                );
+        }
+    }
+}
+
+#[async_trait]
+impl Transport<ClientMessages, MessageFromServer, ClientMessage, ServerMessages, ServerMessage>
+    for RequestIdTransport
+{
+    async fn start(&self) -> TransportResult<ReceiverStream<ClientMessages>>
+    where
+        MessageDispatcher<ClientMessage>:
+            McpDispatch<ClientMessages, ServerMessages, ClientMessage, ServerMessage>,
+    {
+        let mut incoming = self.inner().start().await?.into_inner();
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::spawn(async move {
+            while let Some(mut messages) = incoming.recv().await {
+                match &mut messages {
+                    ClientMessages::Single(message) => tag_message(message),
+                    ClientMessages::Batch(batch) => batch.iter_mut().for_each(tag_message),
+                }
+                if sender.send(messages).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(receiver))
+    }
+
+    fn message_sender(&self) -> Arc<RwLock<Option<MessageDispatcher<ClientMessage>>>> {
+        self.inner().message_sender()
+    }
+
+    fn error_stream(&self) -> &RwLock<Option<IoStream>> {
+        self.inner().error_stream()
+    }
+
+    async fn shut_down(&self) -> TransportResult<()> {
+        self.inner().shut_down().await
+    }
+
+    async fn is_shut_down(&self) -> bool {
+        self.inner().is_shut_down().await
+    }
+
+    async fn consume_string_payload(&self, payload: &str) -> TransportResult<()> {
+        self.inner().consume_string_payload(payload).await
+    }
+
+    async fn pending_request_tx(
+        &self,
+        request_id: &RequestId,
+    ) -> Option<oneshot::Sender<ClientMessage>> {
+        self.inner().pending_request_tx(request_id).await
+    }
+
+    async fn keep_alive(
+        &self,
+        interval: Duration,
+        disconnect_tx: oneshot::Sender<()>,
+    ) -> TransportResult<JoinHandle<()>> {
+        self.inner().keep_alive(interval, disconnect_tx).await
+    }
+
+    async fn session_id(&self) -> Option<SessionId> {
+        self.inner().session_id().await
+    }
+}
+
+#[async_trait]
+impl McpDispatch<ClientMessages, ServerMessages, ClientMessage, ServerMessage>
+    for RequestIdTransport
+{
+    async fn send_message(
+        &self,
+        message: ServerMessages,
+        request_timeout: Option<Duration>,
+    ) -> TransportResult<Option<ClientMessages>> {
+        self.inner.send_message(message, request_timeout).await
+    }
+
+    async fn send(
+        &self,
+        message: ServerMessage,
+        request_timeout: Option<Duration>,
+    ) -> TransportResult<Option<ClientMessage>> {
+        self.inner.send(message, request_timeout).await
+    }
+
+    async fn send_batch(
+        &self,
+        message: Vec<ServerMessage>,
+        request_timeout: Option<Duration>,
+    ) -> TransportResult<Option<Vec<ClientMessage>>> {
+        self.inner.send_batch(message, request_timeout).await
+    }
+
+    async fn write_str(&self, payload: &str, skip_store: bool) -> TransportResult<()> {
+        self.inner.write_str(payload, skip_store).await
+    }
+}
+
+impl
+    TransportDispatcher<
+        ClientMessages,
+        MessageFromServer,
+        ClientMessage,
+        ServerMessages,
+        ServerMessage,
+    > for RequestIdTransport
+{
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rust_mcp_sdk::schema::{
+    CompleteRequestParams, CompleteRequestParamsRef, CompleteResult, CompleteResultCompletion,
+    RpcError,
+};
+use tracing::warn;
+
+use super::prompts::{CATCH_UP_PROMPT, CONVERSATION_URI_TEMPLATE, DRAFT_REPLY_PROMPT};
+use super::{AccountHandler, SignalMcpHandler};
+use crate::signal_cli::{ChatEntry, ChatKind};
+
+/// Most values a completion may return, per the MCP specification.
+const MAX_COMPLETIONS: usize = 100;
+/// How long a `listChats` result serves completions; clients ask on every keystroke.
+const CHATS_TTL: Duration = Duration::from_secs(30);
+
+/// What kind of value an argument takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// Account alias (prompts) or number (resource URIs).
+    Account {
+        by_number: bool,
+    },
+    /// Contact number or group ID.
+    Conversation,
+    Contact,
+}
+
+/// A completion value and the strings it can be found by.
+struct Candidate {
+    value: String,
+    labels: Vec<String>,
+}
+
+impl SignalMcpHandler {
+    pub(super) async fn complete(
+        &self,
+        params: CompleteRequestParams,
+    ) -> std::result::Result<CompleteResult, RpcError> {
+        let argument = params.argument.name.as_str();
+        let source = match &params.ref_ {
+            CompleteRequestParamsRef::PromptReference(prompt) => {
+                if !self.prompts.iter().any(|known| known.name == prompt.name) {
+                    return Err(RpcError::invalid_params()
+                        .with_message(format!("Unknown prompt: {}", prompt.name)));
+                }
+                match (prompt.name.as_str(), argument) {
+                    (_, "account") => Some(Source::Account { by_number: false }),
+                    (CATCH_UP_PROMPT, "conversation") => Some(Source::Conversation),
+                    (DRAFT_REPLY_PROMPT, "contact") => Some(Source::Contact),
+                    _ => None,
+                }
+            }
+            CompleteRequestParamsRef::ResourceTemplateReference(template) => {
+                if template.uri != CONVERSATION_URI_TEMPLATE {
+                    return Err(RpcError::invalid_params()
+                        .with_message(format!("Unknown resource template: {}", template.uri)));
+                }
+                match argument {
+                    "account" => Some(Source::Account { by_number: true }),
+                    "conversation" => Some(Source::Conversation),
+                    _ => None,
+                }
+            }
+        };
+
+        let candidates = match source {
+            Some(Source::Account { by_number }) => self
+                .accounts
+                .iter()
+                .map(|account| Candidate {
+                    value: if by_number {
+                        account.number.clone()
+                    } else {
+                        account.alias.clone()
+                    },
+                    labels: vec![account.alias.clone(), account.number.clone()],
+                })
+                .collect(),
+            Some(source) => {
+                // Later arguments complete against the account already chosen, if any.
+                let context = params
+                    .context
+                    .and_then(|context| context.arguments)
+                    .unwrap_or_default();
+                let account = context
+                    .get("account")
+                    .map(|account| account.trim())
+                    .filter(|account| !account.is_empty());
+                match self.resolve_account(account).ok() {
+                    Some(handler) => handler.completion_candidates(source).await,
+                    None => Vec::new(),
+                }
+            }
+            None => Vec::new(),
+        };
+
+        Ok(completion_result(candidates, &params.argument.value))
+    }
+}
+
+impl AccountHandler {
+    /// Contacts and groups from signal-cli, plus conversations only known to the store.
+    async fn completion_candidates(&self, source: Source) -> Vec<Candidate> {
+        let mut candidates: Vec<Candidate> = match self.completion_chats().await {
+            Ok(chats) => chats
+                .into_iter()
+                .filter(|chat| !chat.blocked)
+                .filter(|chat| source == Source::Conversation || chat.kind == ChatKind::Contact)
+                .map(|chat| Candidate {
+                    labels: std::iter::once(chat.id.clone()).chain(chat.name).collect(),
+                    value: chat.id,
+                })
+                .collect(),
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "signal-cli listChats failed while completing arguments"
+                );
+                Vec::new()
+            }
+        };
+
+        if source == Source::Conversation {
+            match self.store.conversations().await {
+                Ok(conversations) => {
+                    for conversation in conversations {
+                        if !candidates
+                            .iter()
+                            .any(|candidate| candidate.value == conversation.0)
+                        {
+                            candidates.push(Candidate {
+                                labels: vec![conversation.0.clone()],
+                                value: conversation.0,
+                            });
+                        }
+                    }
+                }
+                Err(err) => warn!(?err, "failed to list stored conversations for completion"),
+            }
+        }
+        candidates
+    }
+
+    /// Contacts and groups from signal-cli, reused for [`CHATS_TTL`].
+    ///
+    /// The lock is held while signal-cli runs, so a burst of requests
+    /// shares one call. Failures are not cached.
+    async fn completion_chats(&self) -> anyhow::Result<Vec<ChatEntry>> {
+        let mut cached = self.completion_chats.lock().await;
+        if let Some((fetched, chats)) = cached.as_ref() {
+            if fetched.elapsed() < CHATS_TTL {
+                return Ok(chats.clone());
+            }
+        }
+        let chats = self.signal_cli.list_chats().await?;
+        *cached = Some((Instant::now(), chats.clone()));
+        Ok(chats)
+    }
+}
+
+/// Rank candidates matching `input` best first and keep the first [`MAX_COMPLETIONS`].
+fn completion_result(candidates: Vec<Candidate>, input: &str) -> CompleteResult {
+    let mut ranked: HashMap<String, u8> = HashMap::new();
+    for candidate in candidates {
+        let Some(rank) = candidate
+            .labels
+            .iter()
+            .filter_map(|label| match_rank(label, input))
+            .min()
+        else {
+            continue;
+        };
+        ranked
+            .entry(candidate.value)
+            .and_modify(|best| *best = (*best).min(rank))
+            .or_insert(rank);
+    }
+
+    let mut values: Vec<(u8, String)> = ranked
+        .into_iter()
+        .map(|(value, rank)| (rank, value))
+        .collect();
+    values.sort_by(|(left_rank, left), (right_rank, right)| {
+        left_rank
+            .cmp(right_rank)
+            .then_with(|| left.to_lowercase().cmp(&right.to_lowercase()))
+    });
+    let total = values.len();
+    values.truncate(MAX_COMPLETIONS);
+
+    CompleteResult {
+        completion: CompleteResultCompletion {
+            has_more: Some(total > MAX_COMPLETIONS),
+            total: Some(total as i64),
+            values: values.into_iter().map(|(_, value)| value).collect(),
+        },
+        meta: None,
+    }
+}
+
+/// How well `label` matches what was typed, lower is better: 0 for a prefix
+/// of the whole label, 1 for a prefix of one of its words, 2 when the typed
+/// characters appear in order. Case, `+` and brackets are ignored so numbers
+/// match with or without their formatting.
+fn match_rank(label: &str, input: &str) -> Option<u8> {
+    let normalize = |value: &str| -> String {
+        value
+            .chars()
+            .filter(|ch| !matches!(ch, '+' | '(' | ')'))
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let label = normalize(label);
+    let input = normalize(input.trim());
+
+    if label.starts_with(&input) {
+        return Some(0);
+    }
+    let word_prefix = label
+        .split(|ch: char| ch.is_whitespace() || matches!(ch, '-' | '_' | '.'))
+        .any(|word| word.starts_with(&input));
+    if word_prefix {
+        return Some(1);
+    }
+    let mut remaining = label.chars();
+    input
+        .chars()
+        .all(|wanted| remaining.any(|ch| ch == wanted))
+        .then_some(2)
+}
@@ -0,0 +1,198 @@
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{
+    build_tool, parse_arguments, schema_property, signal_cli_error, string_list_property,
+    text_result, tool_annotations, AccountHandler,
+};
+use crate::signal_cli::ContactUpdate;
+
+pub(super) const UPDATE_CONTACT_TOOL: &str = "signal_update_contact";
+pub(super) const REMOVE_CONTACT_TOOL: &str = "signal_remove_contact";
+pub(super) const BLOCK_TOOL: &str = "signal_block";
+pub(super) const UNBLOCK_TOOL: &str = "signal_unblock";
+
+pub(super) const CONTACT_TOOLS: [&str; 4] = [
+    UPDATE_CONTACT_TOOL,
+    REMOVE_CONTACT_TOOL,
+    BLOCK_TOOL,
+    UNBLOCK_TOOL,
+];
+
+#[derive(Debug, Deserialize)]
+struct UpdateContactArgs {
+    recipient: String,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    nick_given_name: Option<String>,
+    nick_family_name: Option<String>,
+    note: Option<String>,
+    expiration_seconds: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveContactArgs {
+    recipient: String,
+    #[serde(default)]
+    forget: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockArgs {
+    recipients: Vec<String>,
+}
+
+impl AccountHandler {
+    pub(super) async fn invoke_contact_tool(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        match name {
+            UPDATE_CONTACT_TOOL => {
+                let args = parse_arguments::<UpdateContactArgs>(arguments)?;
+                let update = ContactUpdate {
+                    given_name: args.given_name,
+                    family_name: args.family_name,
+                    nick_given_name: args.nick_given_name,
+                    nick_family_name: args.nick_family_name,
+                    note: args.note,
+                    expiration_seconds: args.expiration_seconds,
+                };
+                if update.given_name.is_none()
+                    && update.family_name.is_none()
+                    && update.nick_given_name.is_none()
+                    && update.nick_family_name.is_none()
+                    && update.note.is_none()
+                    && update.expiration_seconds.is_none()
+                {
+                    return Err(CallToolError::from_message(
+                        "provide at least one field to update".to_string(),
+                    ));
+                }
+                self.signal_cli
+                    .update_contact(&args.recipient, &update)
+                    .await
+                    .map_err(|err| signal_cli_error("updateContact", err))?;
+                Ok(text_result(format!("Updated contact {}", args.recipient)))
+            }
+            REMOVE_CONTACT_TOOL => {
+                let args = parse_arguments::<RemoveContactArgs>(arguments)?;
+                self.signal_cli
+                    .remove_contact(&args.recipient, args.forget)
+                    .await
+                    .map_err(|err| signal_cli_error("removeContact", err))?;
+                let action = if args.forget { "Forgot" } else { "Hid" };
+                Ok(text_result(format!(
+                    "{} contact {}",
+                    action, args.recipient
+                )))
+            }
+            BLOCK_TOOL | UNBLOCK_TOOL => {
+                let args = parse_arguments::<BlockArgs>(arguments)?;
+                let recipients: Vec<String> = args
+                    .recipients
+                    .into_iter()
+                    .map(|recipient| recipient.trim().to_string())
+                    .filter(|recipient| !recipient.is_empty())
+                    .collect();
+                if recipients.is_empty() {
+                    return Err(CallToolError::from_message(
+                        "recipients must list at least one number, UUID or group ID".to_string(),
+                    ));
+                }
+
+                let blocked = name == BLOCK_TOOL;
+                let operation = if blocked { "block" } else { "unblock" };
+                self.signal_cli
+                    .set_blocked(&recipients, blocked)
+                    .await
+                    .map_err(|err| signal_cli_error(operation, err))?;
+                let verb = if blocked { "Blocked" } else { "Unblocked" };
+                Ok(text_result(format!("{} {}", verb, recipients.join(", "))))
+            }
+            _ => Err(CallToolError::unknown_tool(name.to_string())),
+        }
+    }
+}
+
+pub(super) fn build_contact_tools() -> Vec<Tool> {
+    vec![
+        build_tool(
+            UPDATE_CONTACT_TOOL,
+            "Update Signal Contact",
+            "Set a contact's name, nickname, note or disappearing-message timer using signal-cli updateContact.",
+            &["recipient"],
+            vec![
+                ("recipient", recipient_property()),
+                ("given_name", schema_property("string", "Given name")),
+                ("family_name", schema_property("string", "Family name")),
+                (
+                    "nick_given_name",
+                    schema_property("string", "Nickname given name, shown instead of the profile name"),
+                ),
+                (
+                    "nick_family_name",
+                    schema_property("string", "Nickname family name"),
+                ),
+                (
+                    "note",
+                    schema_property("string", "Private note about the contact"),
+                ),
+                (
+                    "expiration_seconds",
+                    schema_property(
+                        "integer",
+                        "Disappearing-message timer in seconds (0 disables it)",
+                    ),
+                ),
+            ],
+            tool_annotations(false, false),
+        ),
+        build_tool(
+            REMOVE_CONTACT_TOOL,
+            "Remove Signal Contact",
+            "Hide a contact from the address book, or forget it entirely including identity data.",
+            &["recipient"],
+            vec![
+                ("recipient", recipient_property()),
+                (
+                    "forget",
+                    schema_property(
+                        "boolean",
+                        "Delete all local data about the contact instead of only hiding it",
+                    ),
+                ),
+            ],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            BLOCK_TOOL,
+            "Block Signal Contacts or Groups",
+            "Block contacts or groups so their messages are no longer received.",
+            &["recipients"],
+            vec![(
+                "recipients",
+                string_list_property("E.164 numbers, UUIDs or group IDs to block"),
+            )],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            UNBLOCK_TOOL,
+            "Unblock Signal Contacts or Groups",
+            "Unblock previously blocked contacts or groups.",
+            &["recipients"],
+            vec![(
+                "recipients",
+                string_list_property("E.164 numbers, UUIDs or group IDs to unblock"),
+            )],
+            tool_annotations(false, false),
+        ),
+    ]
+}
+
+fn recipient_property() -> Map<String, Value> {
+    schema_property("string", "Contact in E.164 form or UUID")
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{
+    build_tool, parse_arguments, schema_property, signal_cli_error, text_result, tool_annotations,
+    AccountHandler,
+};
+
+pub(super) const LIST_DEVICES_TOOL: &str = "signal_list_devices";
+pub(super) const ADD_DEVICE_TOOL: &str = "signal_add_device";
+pub(super) const REMOVE_DEVICE_TOOL: &str = "signal_remove_device";
+
+pub(super) const DEVICE_TOOLS: [&str; 3] = [LIST_DEVICES_TOOL, ADD_DEVICE_TOOL, REMOVE_DEVICE_TOOL];
+
+/// signal-cli numbers the primary device 1; it cannot be unlinked.
+const PRIMARY_DEVICE_ID: u64 = 1;
+
+#[derive(Debug, Deserialize)]
+struct AddDeviceArgs {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoveDeviceArgs {
+    device_id: u64,
+}
+
+impl AccountHandler {
+    pub(super) async fn invoke_device_tool(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        match name {
+            LIST_DEVICES_TOOL => self.invoke_list_devices().await,
+            ADD_DEVICE_TOOL => {
+                let args = parse_arguments::<AddDeviceArgs>(arguments)?;
+                let uri = args.uri.trim();
+                if !uri.starts_with("sgnl://linkdevice") && !uri.starts_with("tsdevice:") {
+                    return Err(CallToolError::from_message(
+                        "uri must be the sgnl://linkdevice link shown by the new device"
+                            .to_string(),
+                    ));
+                }
+                self.signal_cli
+                    .add_device(uri)
+                    .await
+                    .map_err(|err| signal_cli_error("addDevice", err))?;
+                Ok(text_result("Linked new device".to_string()))
+            }
+            REMOVE_DEVICE_TOOL => {
+                let args = parse_arguments::<RemoveDeviceArgs>(arguments)?;
+                if args.device_id == PRIMARY_DEVICE_ID {
+                    return Err(CallToolError::from_message(
+                        "device 1 is the primary device and cannot be removed".to_string(),
+                    ));
+                }
+                self.signal_cli
+                    .remove_device(args.device_id)
+                    .await
+                    .map_err(|err| signal_cli_error("removeDevice", err))?;
+                Ok(text_result(format!("Removed device {}", args.device_id)))
+            }
+            _ => Err(CallToolError::unknown_tool(name.to_string())),
+        }
+    }
+
+    async fn invoke_list_devices(&self) -> std::result::Result<CallToolResult, CallToolError> {
+        let devices = self
+            .signal_cli
+            .list_devices()
+            .await
+            .map_err(|err| signal_cli_error("listDevices", err))?;
+
+        if devices.is_empty() {
+            return Ok(text_result("No devices linked.".to_string()));
+        }
+
+        let lines: Vec<String> = devices
+            .iter()
+            .map(|device| {
+                let timestamp = |value: Option<DateTime<Utc>>| {
+                    value
+                        .map(|value| value.to_rfc3339())
+                        .unwrap_or_else(|| "unknown".to_string())
+                };
+                let role = if device.id == PRIMARY_DEVICE_ID {
+                    " (primary)"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}{} — {}, created: {}, last seen: {}",
+                    device.id,
+                    role,
+                    device.name.as_deref().unwrap_or("unnamed"),
+                    timestamp(device.created),
+                    timestamp(device.last_seen)
+                )
+            })
+            .collect();
+        Ok(text_result(lines.join("\n")))
+    }
+}
+
+pub(super) fn build_device_tools() -> Vec<Tool> {
+    vec![
+        build_tool(
+            LIST_DEVICES_TOOL,
+            "List Signal Devices",
+            "List devices linked to the account with their IDs, names, creation and last-seen times using signal-cli listDevices.",
+            &[],
+            Vec::new(),
+            tool_annotations(true, false),
+        ),
+        build_tool(
+            ADD_DEVICE_TOOL,
+            "Link Signal Device",
+            "Link a new device to the account from the sgnl://linkdevice URI it displays, using signal-cli addDevice. The linked device can read and send messages as the account. Only works when this server runs as the primary device.",
+            &["uri"],
+            vec![(
+                "uri",
+                schema_property("string", "Linking URI (sgnl://linkdevice?...) shown by the new device"),
+            )],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            REMOVE_DEVICE_TOOL,
+            "Remove Signal Device",
+            "Unlink a device from the account using signal-cli removeDevice. The device loses access to the account immediately.",
+            &["device_id"],
+            vec![(
+                "device_id",
+                schema_property("integer", "Device ID from signal_list_devices"),
+            )],
+            tool_annotations(false, true),
+        ),
+    ]
+}
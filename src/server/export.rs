@@ -0,0 +1,151 @@
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{
+    CallToolResult, EmbeddedResource, Resource, TextContent, TextResourceContents, Tool,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{build_tool, parse_arguments, schema_property, tool_annotations, SignalMcpHandler};
+use crate::export::{export_conversation, ExportDestination, ExportFormat, ExportRange};
+use crate::mcp::ConversationId;
+
+pub(super) const EXPORT_CONVERSATION_TOOL: &str = "signal_export_conversation";
+
+/// Exports are readable as resources under this prefix followed by `<account>/<file>`.
+pub(super) const EXPORT_URI_PREFIX: &str = "resource://signal/exports/";
+
+#[derive(Debug, Deserialize)]
+struct ExportConversationArgs {
+    conversation_id: String,
+    #[serde(default)]
+    format: ExportFormat,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+impl SignalMcpHandler {
+    pub(super) async fn invoke_export(
+        &self,
+        account: Option<&str>,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let args = parse_arguments::<ExportConversationArgs>(arguments)?;
+        let conversation = args.conversation_id.trim();
+        if conversation.is_empty() {
+            return Err(CallToolError::from_message(
+                "conversation_id must not be empty".to_string(),
+            ));
+        }
+        let range = ExportRange::parse(args.from.as_deref(), args.to.as_deref())
+            .map_err(|err| CallToolError::from_message(format!("{:#}", err)))?;
+        let handler = self.resolve_account(account)?;
+
+        let retention = handler.policy.borrow().retention.clone();
+        let exported = export_conversation(
+            &self.settings,
+            &retention,
+            &handler.number,
+            &handler.store,
+            &ConversationId(conversation.to_string()),
+            args.format,
+            range,
+        )
+        .await
+        .map_err(|err| {
+            CallToolError::from_message(format!("failed to export {}: {:#}", conversation, err))
+        })?;
+
+        let content = match exported.destination {
+            ExportDestination::Saved { path, name } => {
+                let uri = format!("{}{}", EXPORT_URI_PREFIX, name);
+                let summary = format!(
+                    "Exported {} message(s) from {} to {} ({})",
+                    exported.messages,
+                    conversation,
+                    path.display(),
+                    uri
+                );
+                let resource = TextResourceContents {
+                    meta: None,
+                    mime_type: Some(exported.format.mime_type().to_string()),
+                    text: exported.contents,
+                    uri,
+                };
+                vec![
+                    TextContent::new(summary, None, None).into(),
+                    EmbeddedResource::new(resource.into(), None, None).into(),
+                ]
+            }
+            ExportDestination::Inline { reason } => {
+                let summary = format!(
+                    "Exported {} message(s) from {} as {}; not written to disk because {}",
+                    exported.messages,
+                    conversation,
+                    exported.format.mime_type(),
+                    reason
+                );
+                vec![
+                    TextContent::new(summary, None, None).into(),
+                    TextContent::new(exported.contents, None, None).into(),
+                ]
+            }
+        };
+        Ok(CallToolResult {
+            content,
+            is_error: None,
+            meta: None,
+            structured_content: None,
+        })
+    }
+}
+
+/// Resource descriptor for a previous export named `<account>/<file>`.
+pub(super) fn export_resource(name: &str, format: ExportFormat) -> Resource {
+    Resource {
+        annotations: None,
+        description: Some(format!("Conversation export {}", name)),
+        meta: None,
+        mime_type: Some(format.mime_type().to_string()),
+        name: name.to_string(),
+        size: None,
+        title: None,
+        uri: format!("{}{}", EXPORT_URI_PREFIX, name),
+    }
+}
+
+pub(super) fn build_export_tool() -> Tool {
+    build_tool(
+        EXPORT_CONVERSATION_TOOL,
+        "Export Signal Conversation",
+        "Render the locally stored messages of a conversation as Markdown, JSON Lines, self-contained HTML (attachments inlined) or CSV. The file is written under storage/exports/ and returned as an embedded resource; when the store is encrypted, retention limits are set or the messages have disappearing timers, nothing is written and the export is returned as text only.",
+        &["conversation_id"],
+        vec![
+            (
+                "conversation_id",
+                schema_property(
+                    "string",
+                    "Conversation ID as listed by signal_list_conversations (number, UUID or group ID)",
+                ),
+            ),
+            (
+                "format",
+                schema_property("string", "markdown (default), jsonl, html or csv"),
+            ),
+            (
+                "from",
+                schema_property(
+                    "string",
+                    "Earliest message to include: YYYY-MM-DD or RFC 3339 timestamp",
+                ),
+            ),
+            (
+                "to",
+                schema_property(
+                    "string",
+                    "Latest message to include: YYYY-MM-DD (whole day) or RFC 3339 timestamp",
+                ),
+            ),
+        ],
+        tool_annotations(false, false),
+    )
+}
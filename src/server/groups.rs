@@ -0,0 +1,628 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use super::{
+    build_tool, parse_arguments, schema_property, signal_cli_error, string_list_property,
+    text_result, tool_annotations, AccountHandler,
+};
+use crate::signal_cli::{ChatKind, GroupDetails, GroupUpdate};
+
+pub(super) const GET_GROUP_TOOL: &str = "signal_get_group";
+pub(super) const CREATE_GROUP_TOOL: &str = "signal_create_group";
+pub(super) const UPDATE_GROUP_DETAILS_TOOL: &str = "signal_update_group_details";
+pub(super) const ADD_GROUP_MEMBERS_TOOL: &str = "signal_add_group_members";
+pub(super) const REMOVE_GROUP_MEMBERS_TOOL: &str = "signal_remove_group_members";
+pub(super) const SET_GROUP_ADMINS_TOOL: &str = "signal_set_group_admins";
+pub(super) const RESET_GROUP_INVITE_LINK_TOOL: &str = "signal_reset_group_invite_link";
+pub(super) const LEAVE_GROUP_TOOL: &str = "signal_leave_group";
+
+pub(super) const GROUP_TOOLS: [&str; 8] = [
+    GET_GROUP_TOOL,
+    CREATE_GROUP_TOOL,
+    UPDATE_GROUP_DETAILS_TOOL,
+    ADD_GROUP_MEMBERS_TOOL,
+    REMOVE_GROUP_MEMBERS_TOOL,
+    SET_GROUP_ADMINS_TOOL,
+    RESET_GROUP_INVITE_LINK_TOOL,
+    LEAVE_GROUP_TOOL,
+];
+
+#[derive(Debug, Deserialize)]
+struct CreateGroupArgs {
+    name: String,
+    members: Vec<String>,
+    description: Option<String>,
+    avatar: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateGroupDetailsArgs {
+    group_id: String,
+    name: Option<String>,
+    description: Option<String>,
+    avatar: Option<PathBuf>,
+    expiration_seconds: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupMembersArgs {
+    group_id: String,
+    members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupAdminsArgs {
+    group_id: String,
+    #[serde(default)]
+    promote: Vec<String>,
+    #[serde(default)]
+    demote: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupIdArgs {
+    group_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaveGroupArgs {
+    group_id: String,
+    #[serde(default)]
+    delete: bool,
+}
+
+impl AccountHandler {
+    pub(super) async fn invoke_group_tool(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        match name {
+            GET_GROUP_TOOL => {
+                let args = parse_arguments::<GroupIdArgs>(arguments)?;
+                self.invoke_get_group(&args.group_id).await
+            }
+            CREATE_GROUP_TOOL => {
+                let args = parse_arguments::<CreateGroupArgs>(arguments)?;
+                if args.name.trim().is_empty() {
+                    return Err(CallToolError::from_message(
+                        "group name must not be empty".to_string(),
+                    ));
+                }
+                let update = GroupUpdate {
+                    name: Some(args.name.clone()),
+                    description: args.description,
+                    avatar: avatar_path(self.avatars_dir.as_deref(), args.avatar).await?,
+                    add_members: args.members,
+                    ..GroupUpdate::default()
+                };
+                let group_id = self.apply_group_update(None, &update).await?;
+                Ok(text_result(format!(
+                    "Created group \"{}\" with ID {}",
+                    args.name, group_id
+                )))
+            }
+            UPDATE_GROUP_DETAILS_TOOL => {
+                let args = parse_arguments::<UpdateGroupDetailsArgs>(arguments)?;
+                let update = GroupUpdate {
+                    name: args.name,
+                    description: args.description,
+                    avatar: avatar_path(self.avatars_dir.as_deref(), args.avatar).await?,
+                    expiration_seconds: args.expiration_seconds,
+                    ..GroupUpdate::default()
+                };
+                if update.name.is_none()
+                    && update.description.is_none()
+                    && update.avatar.is_none()
+                    && update.expiration_seconds.is_none()
+                {
+                    return Err(CallToolError::from_message(
+                        "provide at least one of name, description, avatar or expiration_seconds"
+                            .to_string(),
+                    ));
+                }
+                let group_id = self
+                    .apply_group_update(Some(&args.group_id), &update)
+                    .await?;
+                Ok(text_result(format!(
+                    "Updated details of group {}",
+                    group_id
+                )))
+            }
+            ADD_GROUP_MEMBERS_TOOL | REMOVE_GROUP_MEMBERS_TOOL => {
+                let args = parse_arguments::<GroupMembersArgs>(arguments)?;
+                require_members(&args.members, "members")?;
+                let count = args.members.len();
+                let adding = name == ADD_GROUP_MEMBERS_TOOL;
+                let update = if adding {
+                    GroupUpdate {
+                        add_members: args.members,
+                        ..GroupUpdate::default()
+                    }
+                } else {
+                    GroupUpdate {
+                        remove_members: args.members,
+                        ..GroupUpdate::default()
+                    }
+                };
+                let group_id = self
+                    .apply_group_update(Some(&args.group_id), &update)
+                    .await?;
+                let verb = if adding { "Added" } else { "Removed" };
+                Ok(text_result(format!(
+                    "{} {} member(s) in group {}",
+                    verb, count, group_id
+                )))
+            }
+            SET_GROUP_ADMINS_TOOL => {
+                let args = parse_arguments::<GroupAdminsArgs>(arguments)?;
+                if args.promote.is_empty() && args.demote.is_empty() {
+                    return Err(CallToolError::from_message(
+                        "provide members to promote or demote".to_string(),
+                    ));
+                }
+                let summary = format!(
+                    "promoted {}, demoted {}",
+                    args.promote.len(),
+                    args.demote.len()
+                );
+                let update = GroupUpdate {
+                    add_admins: args.promote,
+                    remove_admins: args.demote,
+                    ..GroupUpdate::default()
+                };
+                let group_id = self
+                    .apply_group_update(Some(&args.group_id), &update)
+                    .await?;
+                Ok(text_result(format!(
+                    "Updated admins of group {}: {}",
+                    group_id, summary
+                )))
+            }
+            RESET_GROUP_INVITE_LINK_TOOL => {
+                let args = parse_arguments::<GroupIdArgs>(arguments)?;
+                let update = GroupUpdate {
+                    reset_invite_link: true,
+                    ..GroupUpdate::default()
+                };
+                let group_id = self
+                    .apply_group_update(Some(&args.group_id), &update)
+                    .await?;
+                Ok(text_result(format!(
+                    "Reset invite link of group {}; previous links no longer work",
+                    group_id
+                )))
+            }
+            LEAVE_GROUP_TOOL => {
+                let args = parse_arguments::<LeaveGroupArgs>(arguments)?;
+                self.signal_cli
+                    .quit_group(&args.group_id, args.delete)
+                    .await
+                    .map_err(|err| signal_cli_error("quitGroup", err))?;
+                let suffix = if args.delete {
+                    " and deleted local group data"
+                } else {
+                    ""
+                };
+                Ok(text_result(format!(
+                    "Left group {}{}",
+                    args.group_id, suffix
+                )))
+            }
+            _ => Err(CallToolError::unknown_tool(name.to_string())),
+        }
+    }
+
+    async fn invoke_get_group(
+        &self,
+        group_id: &str,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let mut group = self
+            .signal_cli
+            .get_group(group_id)
+            .await
+            .map_err(|err| signal_cli_error("listGroups", err))?;
+
+        // Names only improve readability, so a failed contact lookup is not fatal.
+        match self.signal_cli.list_chats().await {
+            Ok(chats) => {
+                let directory: HashMap<String, String> = chats
+                    .into_iter()
+                    .filter(|chat| chat.kind == ChatKind::Contact)
+                    .filter_map(|chat| chat.name.map(|name| (chat.id, name)))
+                    .collect();
+                for participant in group
+                    .members
+                    .iter_mut()
+                    .chain(group.pending_members.iter_mut())
+                    .chain(group.requesting_members.iter_mut())
+                    .chain(group.admins.iter_mut())
+                {
+                    participant.display_name = directory.get(&participant.address).cloned();
+                }
+            }
+            Err(err) => warn!(
+                ?err,
+                "signal-cli listChats failed while naming group members"
+            ),
+        }
+
+        Ok(text_result(format_group_details(&group)))
+    }
+
+    async fn apply_group_update(
+        &self,
+        group_id: Option<&str>,
+        update: &GroupUpdate,
+    ) -> std::result::Result<String, CallToolError> {
+        self.signal_cli
+            .update_group(group_id, update)
+            .await
+            .map_err(|err| signal_cli_error("updateGroup", err))
+    }
+}
+
+fn format_group_details(group: &GroupDetails) -> String {
+    let unknown = "unknown";
+    let mut lines = vec![
+        format!(
+            "Group: {} ({})",
+            group.name.as_deref().unwrap_or("<unnamed>"),
+            group.id
+        ),
+        format!(
+            "Description: {}",
+            group.description.as_deref().unwrap_or("<none>")
+        ),
+        format!(
+            "We are a member: {}",
+            if group.is_member { "yes" } else { "no" }
+        ),
+    ];
+    if group.is_blocked {
+        lines.push("Blocked: yes".to_string());
+    }
+    lines.push(match group.expiration_seconds {
+        0 => "Disappearing messages: off".to_string(),
+        seconds => format!("Disappearing messages: {} seconds", seconds),
+    });
+    lines.push(format!(
+        "Invite link: {}",
+        group.invite_link.as_deref().unwrap_or("<disabled>")
+    ));
+    lines.push(format!(
+        "Permissions: add members = {}, edit details = {}, send messages = {}",
+        group.permission_add_member.as_deref().unwrap_or(unknown),
+        group.permission_edit_details.as_deref().unwrap_or(unknown),
+        group.permission_send_message.as_deref().unwrap_or(unknown)
+    ));
+
+    for (label, participants) in [
+        ("Admins", &group.admins),
+        ("Members", &group.members),
+        ("Pending members", &group.pending_members),
+        ("Requesting members", &group.requesting_members),
+    ] {
+        lines.push(format!("{} ({}):", label, participants.len()));
+        for participant in participants {
+            match &participant.display_name {
+                Some(name) => lines.push(format!("  - {} ({})", name, participant.address)),
+                None => lines.push(format!("  - {}", participant.address)),
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Resolve a requested avatar to an image file inside `avatars_dir`.
+///
+/// Paths are canonicalized first, so `..` and symlinks cannot point
+/// signal-cli at files outside the directory.
+async fn avatar_path(
+    avatars_dir: Option<&Path>,
+    avatar: Option<PathBuf>,
+) -> std::result::Result<Option<PathBuf>, CallToolError> {
+    let Some(avatar) = avatar else {
+        return Ok(None);
+    };
+    let Some(dir) = avatars_dir else {
+        return Err(CallToolError::from_message(
+            "group avatars are disabled; set `avatars_dir` in the server config to a directory of images to allow them".to_string(),
+        ));
+    };
+    let dir = fs::canonicalize(dir).await.map_err(|err| {
+        warn!(?err, dir = %dir.display(), "avatars directory is unavailable");
+        CallToolError::from_message(format!(
+            "avatars directory {} is unavailable: {}",
+            dir.display(),
+            err
+        ))
+    })?;
+    let path = fs::canonicalize(dir.join(&avatar))
+        .await
+        .ok()
+        .filter(|path| path.starts_with(&dir))
+        .ok_or_else(|| {
+            CallToolError::from_message(format!(
+                "avatar {} is not a file in the avatars directory {}",
+                avatar.display(),
+                dir.display()
+            ))
+        })?;
+    let is_file = fs::metadata(&path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file());
+    if !is_file || !is_image_file(&path).await {
+        return Err(CallToolError::from_message(format!(
+            "avatar {} is not a PNG, JPEG, GIF or WebP image",
+            avatar.display()
+        )));
+    }
+    Ok(Some(path))
+}
+
+/// Whether the file starts with the signature of an image format Signal accepts for avatars.
+async fn is_image_file(path: &Path) -> bool {
+    let mut header = [0u8; 12];
+    let read = match fs::File::open(path).await {
+        Ok(mut file) => file.read(&mut header).await.unwrap_or(0),
+        Err(_) => 0,
+    };
+    let header = &header[..read];
+    header.starts_with(b"\x89PNG\r\n\x1a\n")
+        || header.starts_with(&[0xff, 0xd8, 0xff])
+        || header.starts_with(b"GIF87a")
+        || header.starts_with(b"GIF89a")
+        || (header.starts_with(b"RIFF") && header.get(8..12) == Some(&b"WEBP"[..]))
+}
+
+fn require_members(members: &[String], field: &str) -> std::result::Result<(), CallToolError> {
+    if members.iter().all(|member| member.trim().is_empty()) {
+        return Err(CallToolError::from_message(format!(
+            "{} must list at least one number or UUID",
+            field
+        )));
+    }
+    Ok(())
+}
+
+pub(super) fn build_group_tools() -> Vec<Tool> {
+    vec![
+        build_tool(
+            GET_GROUP_TOOL,
+            "Get Signal Group",
+            "Return a group's members, pending members, admins, description, invite link and permissions using signal-cli listGroups -d.",
+            &["group_id"],
+            vec![("group_id", group_id_property())],
+            tool_annotations(true, false),
+        ),
+        build_tool(
+            CREATE_GROUP_TOOL,
+            "Create Signal Group",
+            "Create a new Signal group with the given members using signal-cli updateGroup.",
+            &["name", "members"],
+            vec![
+                ("name", schema_property("string", "Group name")),
+                (
+                    "members",
+                    string_list_property("Initial members as E.164 numbers or UUIDs"),
+                ),
+                ("description", schema_property("string", "Group description")),
+                (
+                    "avatar",
+                    schema_property(
+                        "string",
+                        "PNG, JPEG, GIF or WebP image to use as the group avatar, relative to the server's avatars directory",
+                    ),
+                ),
+            ],
+            tool_annotations(false, false),
+        ),
+        build_tool(
+            UPDATE_GROUP_DETAILS_TOOL,
+            "Update Signal Group Details",
+            "Rename a group, change its description or avatar, or set the disappearing-message timer.",
+            &["group_id"],
+            vec![
+                ("group_id", group_id_property()),
+                ("name", schema_property("string", "New group name")),
+                ("description", schema_property("string", "New group description")),
+                (
+                    "avatar",
+                    schema_property(
+                        "string",
+                        "PNG, JPEG, GIF or WebP image to use as the group avatar, relative to the server's avatars directory",
+                    ),
+                ),
+                (
+                    "expiration_seconds",
+                    schema_property(
+                        "integer",
+                        "Disappearing-message timer in seconds (0 disables it)",
+                    ),
+                ),
+            ],
+            tool_annotations(false, false),
+        ),
+        build_tool(
+            ADD_GROUP_MEMBERS_TOOL,
+            "Add Signal Group Members",
+            "Add members to a Signal group.",
+            &["group_id", "members"],
+            vec![
+                ("group_id", group_id_property()),
+                (
+                    "members",
+                    string_list_property("Members to add as E.164 numbers or UUIDs"),
+                ),
+            ],
+            tool_annotations(false, false),
+        ),
+        build_tool(
+            REMOVE_GROUP_MEMBERS_TOOL,
+            "Remove Signal Group Members",
+            "Remove members from a Signal group.",
+            &["group_id", "members"],
+            vec![
+                ("group_id", group_id_property()),
+                (
+                    "members",
+                    string_list_property("Members to remove as E.164 numbers or UUIDs"),
+                ),
+            ],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            SET_GROUP_ADMINS_TOOL,
+            "Set Signal Group Admins",
+            "Promote members to group admins or demote existing admins.",
+            &["group_id"],
+            vec![
+                ("group_id", group_id_property()),
+                (
+                    "promote",
+                    string_list_property("Members to grant admin rights"),
+                ),
+                (
+                    "demote",
+                    string_list_property("Admins to revoke admin rights from"),
+                ),
+            ],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            RESET_GROUP_INVITE_LINK_TOOL,
+            "Reset Signal Group Invite Link",
+            "Generate a new group invite link, invalidating the previous one.",
+            &["group_id"],
+            vec![("group_id", group_id_property())],
+            tool_annotations(false, true),
+        ),
+        build_tool(
+            LEAVE_GROUP_TOOL,
+            "Leave Signal Group",
+            "Leave a Signal group using signal-cli quitGroup, optionally deleting local group data.",
+            &["group_id"],
+            vec![
+                ("group_id", group_id_property()),
+                (
+                    "delete",
+                    schema_property(
+                        "boolean",
+                        "Also delete the group and its messages from local storage",
+                    ),
+                ),
+            ],
+            tool_annotations(false, true),
+        ),
+    ]
+}
+
+fn group_id_property() -> Map<String, Value> {
+    schema_property(
+        "string",
+        "Base64 group ID as reported by signal_list_conversations",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Temporary avatars directory, removed again when dropped.
+    struct Scratch {
+        root: PathBuf,
+    }
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "signal-mcp-avatars-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("avatars")).unwrap();
+            std::fs::write(root.join("avatars/logo.png"), b"\x89PNG\r\n\x1a\n rest").unwrap();
+            std::fs::write(root.join("avatars/notes.png"), b"not an image").unwrap();
+            std::fs::write(root.join("secret.png"), b"\x89PNG\r\n\x1a\n secret").unwrap();
+            Self { root }
+        }
+
+        fn avatars(&self) -> PathBuf {
+            self.root.join("avatars")
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    async fn resolve(dir: Option<&Path>, avatar: impl Into<PathBuf>) -> Result<PathBuf, String> {
+        avatar_path(dir, Some(avatar.into()))
+            .await
+            .map(|path| path.expect("an avatar was requested"))
+            .map_err(|err| err.to_string())
+    }
+
+    #[tokio::test]
+    async fn avatars_resolve_to_images_inside_the_directory() {
+        let scratch = Scratch::new("inside");
+        let dir = scratch.avatars();
+        let expected = std::fs::canonicalize(dir.join("logo.png")).unwrap();
+        assert_eq!(resolve(Some(&dir), "logo.png").await.unwrap(), expected);
+        assert_eq!(
+            resolve(Some(&dir), dir.join("logo.png")).await.unwrap(),
+            expected
+        );
+        assert_eq!(avatar_path(None, None).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn avatars_outside_the_directory_are_refused() {
+        let scratch = Scratch::new("outside");
+        let dir = scratch.avatars();
+        for avatar in [
+            PathBuf::from("../secret.png"),
+            scratch.root.join("secret.png"),
+            PathBuf::from("/etc/passwd"),
+            PathBuf::from("missing.png"),
+        ] {
+            let err = resolve(Some(&dir), &avatar).await.unwrap_err();
+            assert!(err.contains("not a file in the avatars directory"), "{err}");
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(scratch.root.join("secret.png"), dir.join("link.png"))
+                .unwrap();
+            assert!(resolve(Some(&dir), "link.png").await.is_err());
+        }
+        let err = resolve(None, "logo.png").await.unwrap_err();
+        assert!(err.contains("avatars are disabled"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn avatars_must_be_image_files() {
+        let scratch = Scratch::new("images");
+        let dir = scratch.avatars();
+        std::fs::create_dir(dir.join("folder.png")).unwrap();
+        for avatar in ["notes.png", "folder.png", "."] {
+            let err = resolve(Some(&dir), avatar).await.unwrap_err();
+            assert!(
+                err.contains("is not a PNG, JPEG, GIF or WebP image"),
+                "{err}"
+            );
+        }
+    }
+}
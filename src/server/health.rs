@@ -0,0 +1,49 @@
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+
+use super::{build_tool, text_result, tool_annotations, SignalMcpHandler};
+use crate::health::{all_passed, format_report, run_checks};
+use crate::settings::AccountSettings;
+
+pub(super) const HEALTH_TOOL: &str = "signal_health";
+
+impl SignalMcpHandler {
+    /// Check the requested account, or every configured account when none is given.
+    pub(super) async fn invoke_health(
+        &self,
+        account: Option<&str>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let accounts = match account {
+            Some(_) => {
+                let handler = self.resolve_account(account)?;
+                vec![AccountSettings {
+                    alias: handler.alias.clone(),
+                    number: handler.number.clone(),
+                }]
+            }
+            None => self.settings.all_accounts(),
+        };
+        let items = run_checks(&self.settings, &accounts).await;
+        let summary = if all_passed(&items) {
+            "All health checks passed."
+        } else {
+            "Some health checks failed."
+        };
+        Ok(text_result(format!(
+            "{}\n{}",
+            summary,
+            format_report(&items)
+        )))
+    }
+}
+
+pub(super) fn build_health_tool() -> Tool {
+    build_tool(
+        HEALTH_TOOL,
+        "Signal Health Check",
+        "Check the signal-cli executable and version, registration and storage of each account (or only `account` when given), daemon connectivity and local storage writability (by writing and removing a probe file). Each item is reported as PASS, FAIL or SKIP.",
+        &[],
+        Vec::new(),
+        tool_annotations(false, false),
+    )
+}
@@ -0,0 +1,195 @@
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use super::{
+    build_tool, parse_arguments, schema_property, signal_cli_error, text_result, tool_annotations,
+    AccountHandler,
+};
+use crate::mcp::ConversationId;
+use crate::store::{IdentityAction, StoredEvent};
+
+pub(super) const LIST_IDENTITIES_TOOL: &str = "signal_list_identities";
+pub(super) const TRUST_IDENTITY_TOOL: &str = "signal_trust_identity";
+
+pub(super) const IDENTITY_TOOLS: [&str; 2] = [LIST_IDENTITIES_TOOL, TRUST_IDENTITY_TOOL];
+
+#[derive(Debug, Default, Deserialize)]
+struct ListIdentitiesArgs {
+    recipient: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrustIdentityArgs {
+    recipient: String,
+    safety_number: Option<String>,
+    #[serde(default)]
+    trust_all_known_keys: bool,
+}
+
+impl AccountHandler {
+    pub(super) async fn invoke_identity_tool(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        match name {
+            LIST_IDENTITIES_TOOL => {
+                let args = parse_arguments::<ListIdentitiesArgs>(arguments)?;
+                self.invoke_list_identities(args).await
+            }
+            TRUST_IDENTITY_TOOL => {
+                let args = parse_arguments::<TrustIdentityArgs>(arguments)?;
+                self.invoke_trust_identity(args).await
+            }
+            _ => Err(CallToolError::unknown_tool(name.to_string())),
+        }
+    }
+
+    async fn invoke_list_identities(
+        &self,
+        args: ListIdentitiesArgs,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let identities = self
+            .signal_cli
+            .list_identities(args.recipient.as_deref())
+            .await
+            .map_err(|err| signal_cli_error("listIdentities", err))?;
+
+        if identities.is_empty() {
+            return Ok(text_result("No identity keys known.".to_string()));
+        }
+
+        let mut lines = Vec::with_capacity(identities.len());
+        for identity in &identities {
+            let added = identity
+                .added
+                .map(|added| added.to_rfc3339())
+                .unwrap_or_else(|| "unknown".to_string());
+            lines.push(format!(
+                "{} — trust: {}, added: {}",
+                identity.address,
+                identity.trust_level.as_deref().unwrap_or("unknown"),
+                added
+            ));
+            if let Some(safety_number) = &identity.safety_number {
+                lines.push(format!("  safety number: {}", safety_number));
+            }
+            if let Some(fingerprint) = &identity.fingerprint {
+                lines.push(format!("  fingerprint: {}", fingerprint));
+            }
+        }
+
+        if let Some(recipient) = &args.recipient {
+            match self.store.events(&ConversationId(recipient.clone())).await {
+                Ok(events) => {
+                    let changes: Vec<String> = events
+                        .iter()
+                        .filter_map(|event| match event {
+                            StoredEvent::IdentityChange(change) => Some(format!(
+                                "  {} — {}",
+                                change.detected_at.to_rfc3339(),
+                                describe_action(change.action)
+                            )),
+                            _ => None,
+                        })
+                        .collect();
+                    if !changes.is_empty() {
+                        lines.push("Recorded identity changes:".to_string());
+                        lines.extend(changes);
+                    }
+                }
+                Err(err) => warn!(?err, %recipient, "failed to read identity history"),
+            }
+        }
+
+        Ok(text_result(lines.join("\n")))
+    }
+
+    async fn invoke_trust_identity(
+        &self,
+        args: TrustIdentityArgs,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let safety_number = args
+            .safety_number
+            .as_deref()
+            .map(str::trim)
+            .filter(|number| !number.is_empty());
+        if safety_number.is_none() && !args.trust_all_known_keys {
+            return Err(CallToolError::from_message(
+                "provide the verified safety_number, or set trust_all_known_keys to trust without verification"
+                    .to_string(),
+            ));
+        }
+
+        self.signal_cli
+            .trust_identity(&args.recipient, safety_number)
+            .await
+            .map_err(|err| signal_cli_error("trust", err))?;
+        self.record_identity_change(&args.recipient, IdentityAction::Trusted)
+            .await;
+
+        let how = if safety_number.is_some() {
+            "verified safety number"
+        } else {
+            "all known keys, unverified"
+        };
+        Ok(text_result(format!(
+            "Trusted identity of {} ({})",
+            args.recipient, how
+        )))
+    }
+}
+
+fn describe_action(action: IdentityAction) -> &'static str {
+    match action {
+        IdentityAction::SendBlocked => "send blocked by untrusted identity",
+        IdentityAction::AutoTrusted => "new identity trusted automatically",
+        IdentityAction::Trusted => "identity trusted by operator",
+    }
+}
+
+pub(super) fn build_identity_tools() -> Vec<Tool> {
+    vec![
+        build_tool(
+            LIST_IDENTITIES_TOOL,
+            "List Signal Identities",
+            "List identity keys, safety numbers and trust levels using signal-cli listIdentities, plus recorded identity changes for a single contact.",
+            &[],
+            vec![(
+                "recipient",
+                schema_property("string", "Limit the listing to one contact (E.164 or UUID)"),
+            )],
+            tool_annotations(true, false),
+        ),
+        build_tool(
+            TRUST_IDENTITY_TOOL,
+            "Trust Signal Identity",
+            "Trust a contact's identity key after a safety number change using signal-cli trust.",
+            &["recipient"],
+            vec![
+                (
+                    "recipient",
+                    schema_property("string", "Contact in E.164 form or UUID"),
+                ),
+                (
+                    "safety_number",
+                    schema_property(
+                        "string",
+                        "Safety number verified out of band with the contact",
+                    ),
+                ),
+                (
+                    "trust_all_known_keys",
+                    schema_property(
+                        "boolean",
+                        "Trust every known key without verifying a safety number",
+                    ),
+                ),
+            ],
+            tool_annotations(false, false),
+        ),
+    ]
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use rust_mcp_sdk::schema::{
+    EmbeddedResource, GetPromptResult, Prompt, PromptArgument, PromptMessage, ResourceTemplate,
+    Role, RpcError, TextContent, TextResourceContents,
+};
+
+use super::{format_message, sent_by, AccountHandler, SignalMcpHandler, SEND_MESSAGE_TOOL};
+use crate::export::conversation_transcript;
+use crate::mcp::{ConversationId, Message, MessageBody};
+use crate::store::StoredEvent;
+
+pub(super) const CATCH_UP_PROMPT: &str = "signal_catch_up";
+pub(super) const DRAFT_REPLY_PROMPT: &str = "signal_draft_reply";
+pub(super) const SUMMARIZE_UNREAD_PROMPT: &str = "signal_summarize_unread";
+
+/// Stored conversations are readable as resources under this prefix followed by `<number>/<conversation>`.
+pub(super) const CONVERSATION_URI_PREFIX: &str = "resource://signal/conversations/";
+/// RFC 6570 form of the conversation URIs, advertised as a resource template.
+pub(super) const CONVERSATION_URI_TEMPLATE: &str =
+    "resource://signal/conversations/{account}/{conversation}";
+
+/// Latest messages included in a conversation transcript; older ones are omitted.
+const TRANSCRIPT_LIMIT: usize = 200;
+/// Seconds the unread prompt lets signal-cli deliver queued messages.
+const UNREAD_RECEIVE_TIMEOUT_SECONDS: u64 = 1;
+
+impl SignalMcpHandler {
+    pub(super) async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> std::result::Result<GetPromptResult, RpcError> {
+        let argument = |key: &str| {
+            arguments
+                .get(key)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |key: &str| {
+            argument(key).ok_or_else(|| {
+                RpcError::invalid_params()
+                    .with_message(format!("prompt {} requires `{}`", name, key))
+            })
+        };
+        let handler = self
+            .resolve_account(argument("account"))
+            .map_err(|err| RpcError::invalid_params().with_message(err.to_string()))?;
+
+        match name {
+            CATCH_UP_PROMPT => handler.catch_up_prompt(required("conversation")?).await,
+            DRAFT_REPLY_PROMPT => handler.draft_reply_prompt(required("contact")?).await,
+            SUMMARIZE_UNREAD_PROMPT => handler.summarize_unread_prompt().await,
+            _ => Err(RpcError::invalid_params().with_message(format!("Unknown prompt: {}", name))),
+        }
+    }
+
+    /// Read the `<number>/<conversation>` transcript named after [`CONVERSATION_URI_PREFIX`].
+    pub(super) async fn read_conversation_resource(
+        &self,
+        name: &str,
+    ) -> std::result::Result<TextResourceContents, RpcError> {
+        let Some((account, conversation)) = name
+            .split_once('/')
+            .filter(|(account, conversation)| !account.is_empty() && !conversation.is_empty())
+        else {
+            return Err(RpcError::invalid_params().with_message(
+                "conversation resources have the form <number>/<conversation>".to_string(),
+            ));
+        };
+        // Clients expanding the template percent-encode the `+` of numbers and the
+        // `/`, `+` and `=` of group IDs.
+        let handler = self
+            .resolve_account(Some(&percent_decode(account)))
+            .map_err(|err| RpcError::invalid_params().with_message(err.to_string()))?;
+        handler
+            .conversation_resource(&ConversationId(percent_decode(conversation)))
+            .await
+    }
+}
+
+impl AccountHandler {
+    async fn catch_up_prompt(
+        &self,
+        conversation: &str,
+    ) -> std::result::Result<GetPromptResult, RpcError> {
+        let resource = self
+            .conversation_resource(&ConversationId(conversation.to_string()))
+            .await?;
+        let request = format!(
+            "Catch me up on the Signal conversation {}. Using the attached transcript, summarize what was discussed, decisions made and open questions, and point out anything that still needs a reply from me, most important first.",
+            conversation
+        );
+        Ok(prompt_result(
+            format!("Catch up on {}", conversation),
+            request,
+            vec![resource],
+        ))
+    }
+
+    async fn draft_reply_prompt(
+        &self,
+        contact: &str,
+    ) -> std::result::Result<GetPromptResult, RpcError> {
+        let message = self.last_message_from(contact).await?.ok_or_else(|| {
+            RpcError::invalid_params().with_message(format!(
+                "no stored message from {}; receive messages first",
+                contact
+            ))
+        })?;
+        let resource = self.conversation_resource(&message.conversation_id).await?;
+        let request = format!(
+            "Draft a reply to the last message {} sent me on Signal:\n\n{}\n\nThe conversation so far is attached. Match its tone and language and keep the reply brief. Only draft it; once I approve, send it with {} to recipient {}.",
+            contact,
+            format_message(&message),
+            SEND_MESSAGE_TOOL,
+            message.conversation_id.0
+        );
+        Ok(prompt_result(
+            format!("Draft a reply to {}", contact),
+            request,
+            vec![resource],
+        ))
+    }
+
+    /// Unread means still queued in signal-cli; fetching them also stores them.
+    async fn summarize_unread_prompt(&self) -> std::result::Result<GetPromptResult, RpcError> {
+        let unread = self
+            .receive_and_store(UNREAD_RECEIVE_TIMEOUT_SECONDS)
+            .await
+            .map_err(|err| RpcError::internal_error().with_message(err.to_string()))?;
+        if unread.is_empty() {
+            return Ok(prompt_result(
+                "Summarize unread Signal messages".to_string(),
+                "Summarize my unread Signal messages. There are none right now, so just tell me I am caught up.".to_string(),
+                Vec::new(),
+            ));
+        }
+
+        let mut conversations: Vec<&ConversationId> = Vec::new();
+        for message in &unread {
+            if !conversations.contains(&&message.conversation_id) {
+                conversations.push(&message.conversation_id);
+            }
+        }
+        let mut resources = Vec::with_capacity(conversations.len());
+        for conversation in &conversations {
+            resources.push(self.conversation_resource(conversation).await?);
+        }
+        let request = format!(
+            "Summarize my {} unread Signal message(s) across {} conversation(s), grouped by conversation, and point out anything that needs a reply. The new messages are:\n\n{}\n\nEach conversation's recent history is attached for context.",
+            unread.len(),
+            conversations.len(),
+            unread
+                .iter()
+                .map(format_message)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        Ok(prompt_result(
+            "Summarize unread Signal messages".to_string(),
+            request,
+            resources,
+        ))
+    }
+
+    /// Latest stored message from `contact` (address or display name) in any conversation.
+    async fn last_message_from(
+        &self,
+        contact: &str,
+    ) -> std::result::Result<Option<Message>, RpcError> {
+        let store_error = |err: anyhow::Error| {
+            RpcError::internal_error().with_message(format!("failed to read store: {:#}", err))
+        };
+        let mut latest: Option<Message> = None;
+        for conversation in self.store.conversations().await.map_err(store_error)? {
+            for event in self
+                .store
+                .events(&conversation)
+                .await
+                .map_err(store_error)?
+            {
+                let StoredEvent::Message(message) = event else {
+                    continue;
+                };
+                if !sent_by(&message, contact) || matches!(message.body, MessageBody::Unknown) {
+                    continue;
+                }
+                if latest
+                    .as_ref()
+                    .is_none_or(|latest| message.timestamp > latest.timestamp)
+                {
+                    latest = Some(message);
+                }
+            }
+        }
+        Ok(latest)
+    }
+
+    async fn conversation_resource(
+        &self,
+        conversation: &ConversationId,
+    ) -> std::result::Result<TextResourceContents, RpcError> {
+        let text = conversation_transcript(&self.store, conversation, TRANSCRIPT_LIMIT)
+            .await
+            .map_err(|err| {
+                RpcError::internal_error().with_message(format!(
+                    "failed to read conversation {}: {:#}",
+                    conversation.0, err
+                ))
+            })?;
+        Ok(TextResourceContents {
+            meta: None,
+            mime_type: Some("text/markdown".to_string()),
+            text,
+            uri: format!(
+                "{}{}/{}",
+                CONVERSATION_URI_PREFIX, self.number, conversation.0
+            ),
+        })
+    }
+}
+
+pub(super) fn conversation_resource_template() -> ResourceTemplate {
+    ResourceTemplate {
+        annotations: None,
+        description: Some(
+            "Markdown transcript of the latest stored messages of a conversation; `account` is the account number, `conversation` a contact number, UUID or group ID."
+                .into(),
+        ),
+        meta: None,
+        mime_type: Some("text/markdown".into()),
+        name: "signal.conversation".into(),
+        title: Some("Signal Conversation".into()),
+        uri_template: CONVERSATION_URI_TEMPLATE.into(),
+    }
+}
+
+/// Decode `%XX` escapes, leaving malformed ones as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A single user turn: the request text followed by the embedded transcripts.
+fn prompt_result(
+    description: String,
+    request: String,
+    resources: Vec<TextResourceContents>,
+) -> GetPromptResult {
+    let mut messages = vec![PromptMessage {
+        content: TextContent::new(request, None, None).into(),
+        role: Role::User,
+    }];
+    messages.extend(resources.into_iter().map(|resource| PromptMessage {
+        content: EmbeddedResource::new(resource.into(), None, None).into(),
+        role: Role::User,
+    }));
+    GetPromptResult {
+        description: Some(description),
+        messages,
+        meta: None,
+    }
+}
+
+pub(super) fn build_prompts() -> Vec<Prompt> {
+    let account = prompt_argument(
+        "account",
+        "Account alias or number (default: the first configured account)",
+        false,
+    );
+    vec![
+        Prompt {
+            arguments: vec![
+                prompt_argument(
+                    "conversation",
+                    "Conversation ID (number, UUID or group ID) to catch up on",
+                    true,
+                ),
+                account.clone(),
+            ],
+            description: Some(
+                "Catch me up on a conversation, with its stored transcript embedded.".into(),
+            ),
+            meta: None,
+            name: CATCH_UP_PROMPT.to_string(),
+            title: Some("Catch Up on Conversation".into()),
+        },
+        Prompt {
+            arguments: vec![
+                prompt_argument(
+                    "contact",
+                    "Sender address or display name whose last message to answer",
+                    true,
+                ),
+                account.clone(),
+            ],
+            description: Some(
+                "Draft a reply to the last stored message from a contact, with the conversation embedded."
+                    .into(),
+            ),
+            meta: None,
+            name: DRAFT_REPLY_PROMPT.to_string(),
+            title: Some("Draft Reply".into()),
+        },
+        Prompt {
+            arguments: vec![account],
+            description: Some(
+                "Fetch messages still queued in signal-cli and summarize them, with each conversation embedded."
+                    .into(),
+            ),
+            meta: None,
+            name: SUMMARIZE_UNREAD_PROMPT.to_string(),
+            title: Some("Summarize Unread Messages".into()),
+        },
+    ]
+}
+
+fn prompt_argument(name: &str, description: &str, required: bool) -> PromptArgument {
+    PromptArgument {
+        description: Some(description.into()),
+        name: name.to_string(),
+        required: Some(required),
+        title: None,
+    }
+}
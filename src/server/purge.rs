@@ -0,0 +1,60 @@
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{CallToolResult, Tool};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::{
+    build_tool, parse_arguments, schema_property, text_result, tool_annotations, AccountHandler,
+};
+use crate::mcp::ConversationId;
+
+pub(super) const PURGE_CONVERSATION_TOOL: &str = "signal_purge_conversation";
+
+#[derive(Debug, Deserialize)]
+struct PurgeConversationArgs {
+    conversation_id: String,
+}
+
+impl AccountHandler {
+    pub(super) async fn invoke_purge_conversation(
+        &self,
+        arguments: Option<Map<String, Value>>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let args = parse_arguments::<PurgeConversationArgs>(arguments)?;
+        let conversation = args.conversation_id.trim();
+        if conversation.is_empty() {
+            return Err(CallToolError::from_message(
+                "conversation_id must not be empty".to_string(),
+            ));
+        }
+
+        let removed = self
+            .retention
+            .purge(&self.store, &ConversationId(conversation.to_string()))
+            .await
+            .map_err(|err| {
+                CallToolError::from_message(format!("failed to purge {}: {:#}", conversation, err))
+            })?;
+        Ok(text_result(format!(
+            "Purged {} stored event(s) and {} attachment(s) for {}",
+            removed.events, removed.attachments, conversation
+        )))
+    }
+}
+
+pub(super) fn build_purge_tool() -> Tool {
+    build_tool(
+        PURGE_CONVERSATION_TOOL,
+        "Purge Signal Conversation",
+        "Permanently delete the locally stored history of a conversation and the downloaded attachments it references. Messages on Signal itself and on other devices are not affected.",
+        &["conversation_id"],
+        vec![(
+            "conversation_id",
+            schema_property(
+                "string",
+                "Conversation ID as listed by signal_list_conversations (number, UUID or group ID)",
+            ),
+        )],
+        tool_annotations(false, true),
+    )
+}
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::{Regex, RegexBuilder};
+use rust_mcp_sdk::schema::schema_utils::CallToolError;
+use rust_mcp_sdk::schema::{
+    CallToolResult, ProgressNotification, ProgressNotificationParams, ProgressToken, TextContent,
+    Tool,
+};
+use rust_mcp_sdk::McpServer;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::time::{sleep, Instant};
+use tracing::warn;
+
+use super::{
+    build_tool, format_message, parse_arguments, schema_property, sent_by, tool_annotations,
+    SignalMcpHandler,
+};
+use crate::mcp::{Message, MessageBody};
+
+pub(super) const WAIT_FOR_MESSAGE_TOOL: &str = "signal_wait_for_message";
+const DEFAULT_WAIT_TIMEOUT_SECONDS: u64 = 60;
+const MAX_WAIT_TIMEOUT_SECONDS: u64 = 600;
+/// Longest single `signal-cli receive`; progress is reported after each one.
+const POLL_SECONDS: u64 = 5;
+/// Floor on the time between polls, as signal-cli returns early once its queue drains.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+struct WaitForMessageArgs {
+    conversation_id: Option<String>,
+    author: Option<String>,
+    pattern: Option<String>,
+    timeout_seconds: Option<u64>,
+    progress_token: Option<ProgressToken>,
+}
+
+/// Which incoming message ends the wait; unset criteria match anything.
+struct MessageFilter {
+    conversation: Option<String>,
+    author: Option<String>,
+    pattern: Option<Regex>,
+}
+
+impl MessageFilter {
+    fn new(args: &WaitForMessageArgs) -> std::result::Result<Self, CallToolError> {
+        let non_empty = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let pattern = non_empty(&args.pattern)
+            .map(|pattern| {
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| CallToolError::from_message(format!("invalid pattern: {}", err)))
+            })
+            .transpose()?;
+        Ok(Self {
+            conversation: non_empty(&args.conversation_id),
+            author: non_empty(&args.author),
+            pattern,
+        })
+    }
+
+    fn matches(&self, message: &Message) -> bool {
+        if let Some(conversation) = &self.conversation {
+            if message.conversation_id.0 != *conversation {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            if !sent_by(message, author) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            let MessageBody::Text { text, .. } = &message.body else {
+                return false;
+            };
+            if !pattern.is_match(text) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl SignalMcpHandler {
+    /// Receive until a message passes the filter or the timeout elapses.
+    ///
+    /// Non-matching messages are stored like any other receive and listed in
+    /// the result, since signal-cli has already removed them from its queue.
+    pub(super) async fn invoke_wait_for_message(
+        &self,
+        account: Option<&str>,
+        arguments: Option<Map<String, Value>>,
+        runtime: Arc<dyn McpServer>,
+    ) -> std::result::Result<CallToolResult, CallToolError> {
+        let args = parse_arguments::<WaitForMessageArgs>(arguments)?;
+        let filter = MessageFilter::new(&args)?;
+        let timeout = args
+            .timeout_seconds
+            .unwrap_or(DEFAULT_WAIT_TIMEOUT_SECONDS)
+            .clamp(1, MAX_WAIT_TIMEOUT_SECONDS);
+        let handler = self.resolve_account(account)?;
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(timeout);
+        let mut matched = None;
+        let mut others = Vec::new();
+        while matched.is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let polled_at = Instant::now();
+            let poll = remaining.as_secs().clamp(1, POLL_SECONDS);
+            for message in handler.receive_and_store(poll).await? {
+                if matched.is_none() && filter.matches(&message) {
+                    matched = Some(message);
+                } else {
+                    others.push(message);
+                }
+            }
+            if matched.is_some() {
+                break;
+            }
+
+            if let Some(token) = &args.progress_token {
+                let elapsed = started.elapsed().as_secs().min(timeout);
+                let params = ProgressNotificationParams {
+                    message: Some(format!(
+                        "Waiting for a matching message ({} other received)",
+                        others.len()
+                    )),
+                    progress: elapsed as f64,
+                    progress_token: token.clone(),
+                    total: Some(timeout as f64),
+                };
+                if let Err(err) = runtime
+                    .send_notification(ProgressNotification::new(params).into())
+                    .await
+                {
+                    warn!(?err, "failed to send wait progress notification");
+                }
+            }
+            let idle = MIN_POLL_INTERVAL.saturating_sub(polled_at.elapsed());
+            if !idle.is_zero() {
+                sleep(idle.min(deadline.saturating_duration_since(Instant::now()))).await;
+            }
+        }
+
+        let mut body = match &matched {
+            Some(message) => format!("Matching message:\n{}", format_message(message)),
+            None => format!("No matching message arrived within {}s.", timeout),
+        };
+        if !others.is_empty() {
+            body.push_str(&format!(
+                "\n\nOther messages received while waiting (stored locally):\n{}",
+                others
+                    .iter()
+                    .map(format_message)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        let mut content = vec![TextContent::new(body, None, None).into()];
+        let structured_content = match matched {
+            Some(message) => {
+                let value = serde_json::to_value(&message).map_err(|err| {
+                    CallToolError::from_message(format!("failed to encode message: {}", err))
+                })?;
+                content.push(TextContent::new(value.to_string(), None, None).into());
+                let mut structured = Map::new();
+                structured.insert("message".to_string(), value);
+                Some(structured)
+            }
+            None => None,
+        };
+        Ok(CallToolResult {
+            content,
+            is_error: None,
+            meta: None,
+            structured_content,
+        })
+    }
+}
+
+pub(super) fn build_wait_for_message_tool() -> Tool {
+    build_tool(
+        WAIT_FOR_MESSAGE_TOOL,
+        "Wait for Signal Message",
+        "Block until a new message matching every given filter arrives, or the timeout elapses. The match is returned as text and as a JSON message object; other messages received meanwhile are stored and listed. Pair with signal_send_message to ask and wait for a reply.",
+        &[],
+        vec![
+            (
+                "conversation_id",
+                schema_property(
+                    "string",
+                    "Only match messages in this conversation (number, UUID or group ID)",
+                ),
+            ),
+            (
+                "author",
+                schema_property(
+                    "string",
+                    "Only match messages from this sender address or display name",
+                ),
+            ),
+            (
+                "pattern",
+                schema_property(
+                    "string",
+                    "Case-insensitive regular expression the message text must match",
+                ),
+            ),
+            (
+                "timeout_seconds",
+                schema_property(
+                    "integer",
+                    "Seconds to wait before giving up (default 60, max 600)",
+                ),
+            ),
+            (
+                "progress_token",
+                schema_property(
+                    "string",
+                    "Token to tag notifications/progress updates with while waiting",
+                ),
+            ),
+        ],
+        tool_annotations(false, false),
+    )
+}
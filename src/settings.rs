@@ -1,34 +1,789 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     #[serde(default = "default_signal_cli_path")]
     pub signal_cli_path: PathBuf,
+    #[serde(default)]
     pub account: String,
+    #[serde(default)]
+    pub accounts: Vec<AccountSettings>,
     #[serde(default = "default_storage_directory")]
     pub storage: PathBuf,
+    #[serde(default)]
+    pub identity_policy: IdentityPolicy,
+    /// signal-cli daemon socket probed by health checks; defaults to
+    /// `$XDG_RUNTIME_DIR/signal-cli/socket` when that exists.
+    #[serde(default)]
+    pub daemon_socket: Option<PathBuf>,
+    #[serde(default)]
+    pub transport: TransportKind,
+    #[serde(default = "default_http_host")]
+    pub http_host: String,
+    #[serde(default = "default_http_port")]
+    pub http_port: u16,
+    /// Allow an `http_host` other than loopback. The HTTP endpoint has no
+    /// authentication, so anyone who can reach it can use the accounts.
+    #[serde(default)]
+    pub http_allow_remote: bool,
+    #[serde(default)]
+    pub encryption: Option<EncryptionSettings>,
+    #[serde(default)]
+    pub retention: RetentionSettings,
+    /// signal-cli's attachment directory, cleared alongside purged messages;
+    /// defaults to `$XDG_DATA_HOME/signal-cli/attachments`.
+    #[serde(default)]
+    pub attachments_dir: Option<PathBuf>,
+    /// Directory group avatars are read from. Without it the group tools
+    /// refuse avatars, since clients could otherwise upload any host file.
+    #[serde(default)]
+    pub avatars_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    #[serde(default)]
+    pub timeouts: TimeoutSettings,
+    /// Config file the settings were read from, if any.
+    #[serde(skip)]
+    pub config_path: Option<PathBuf>,
+}
+
+/// Where the store encryption secret lives; the secret itself is never inline.
+///
+/// Covers the conversation store and attachments imported from backups.
+/// Attachments signal-cli downloads stay plaintext in its own directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct EncryptionSettings {
+    /// File whose raw bytes are the secret, e.g. 32 random bytes.
+    pub key_file: Option<PathBuf>,
+    /// File holding a passphrase; a trailing newline is ignored.
+    pub passphrase_file: Option<PathBuf>,
+}
+
+/// Limits on how much history the local store keeps. A limit of 0 disables it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RetentionSettings {
+    /// Drop stored events older than this many days.
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// Keep at most this many messages per conversation, newest first.
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    /// How often the retention job runs while serving.
+    #[serde(default = "default_retention_interval")]
+    pub interval_minutes: u64,
+    /// Per-conversation limits that replace the defaults above when set.
+    #[serde(default)]
+    pub conversations: Vec<ConversationRetention>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ConversationRetention {
+    /// Conversation ID: a contact's number or UUID, or a group ID.
+    pub conversation: String,
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+}
+
+/// Retention limits resolved for one conversation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionRule {
+    pub max_age_days: Option<u32>,
+    pub max_messages: Option<usize>,
+}
+
+/// How long a signal-cli invocation may run before it is killed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TimeoutSettings {
+    /// Seconds for any operation not listed in `operations`.
+    #[serde(default = "default_timeout_seconds")]
+    pub default_seconds: u64,
+    /// Seconds per signal-cli command, e.g. `send = 120` or `listGroups = 30`.
+    /// `receive` additionally gets the wait it was asked for.
+    #[serde(default)]
+    pub operations: BTreeMap<String, u64>,
+}
+
+/// Where the server's own logs go. Never stdout, which belongs to the stdio transport.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LoggingSettings {
+    #[serde(default = "default_log_outputs")]
+    pub outputs: Vec<LogOutput>,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Default level (`error` to `trace`); `RUST_LOG` replaces this and `modules` when set.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Per-module levels, e.g. `"signal_mcp_server::signal_cli" = "debug"`.
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+    /// Directory for `file` output; defaults to `storage/logs`.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Rotated files to keep; 0 keeps them all.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    Stderr,
+    /// Rotating files under [`LoggingSettings::directory`].
+    File,
+    /// The systemd journal, with fields as journal fields.
+    Journald,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per event; does not apply to journald.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+/// How MCP clients reach the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// JSON-RPC over stdin/stdout, for clients that spawn the server.
+    #[default]
+    Stdio,
+    /// Streamable HTTP on `http_host:http_port`.
+    Http,
+}
+
+/// Values given on the command line; they take precedence over the config
+/// file and `SIGNAL_MCP__` environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub config: Option<PathBuf>,
+    pub account: Option<String>,
+    pub signal_cli_path: Option<PathBuf>,
+    pub storage: Option<PathBuf>,
+    pub transport: Option<String>,
+}
+
+/// An additional Signal account, addressed in tool calls by `alias` or `number`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AccountSettings {
+    pub alias: String,
+    pub number: String,
+}
+
+/// How sends react when a contact's safety number changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityPolicy {
+    /// Refuse sends to any identity that has not been trusted explicitly.
+    Block,
+    /// Trust changed identities automatically, but report the change.
+    Warn,
+    /// Trust the first identity seen for a contact; refuse sends after it changes.
+    #[default]
+    TrustOnFirstUse,
+}
+
+/// Settings that take effect without a restart when the configuration is reloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimePolicy {
+    pub identity_policy: IdentityPolicy,
+    pub retention: RetentionSettings,
+}
+
+/// Alias under which the top-level `account` is served.
+pub const DEFAULT_ACCOUNT_ALIAS: &str = "default";
+
+/// Config file name looked up in the working directory and XDG config directories.
+pub const CONFIG_FILE: &str = "config.toml";
+
+const APP_DIR: &str = "signal-mcp-server";
+
+/// What the loaded settings are for, which decides the environment checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    /// Serving MCP clients, which needs signal-cli and its daemon socket.
+    Serve,
+    /// Commands that only work on the local store (`import`, `export`, `purge`).
+    StoreOnly,
 }
 
 impl Settings {
-    pub fn load() -> Result<Self> {
-        let builder = Config::builder()
-            .add_source(File::with_name("config").required(false))
-            .add_source(Environment::with_prefix("SIGNAL_MCP").separator("__"));
+    pub fn load(overrides: &Overrides, purpose: Purpose) -> Result<Self> {
+        let settings = Self::load_without_account(overrides)?;
+        let problems = settings.validate(purpose);
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "invalid configuration:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {}", problem))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        Ok(settings)
+    }
+
+    /// Check everything `purpose` relies on and describe each problem found.
+    ///
+    /// All checks run so the operator sees every problem at once; an empty
+    /// list means the configuration is usable. Nothing is created or written.
+    pub fn validate(&self, purpose: Purpose) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let accounts = self.all_accounts();
+        if accounts.is_empty() {
+            problems.push(
+                "account: no Signal account configured; run `signal-mcp-server link` or `register`/`verify` first"
+                    .to_string(),
+            );
+        }
+        for (index, account) in accounts.iter().enumerate() {
+            if !is_e164(&account.number) {
+                problems.push(format!(
+                    "account {:?}: {:?} is not an E.164 number (expected `+` and 7-15 digits, e.g. +15551234567)",
+                    account.alias, account.number
+                ));
+            }
+            if account.alias.trim().is_empty() {
+                problems.push(format!("accounts: {} has an empty alias", account.number));
+            }
+            if let Some(other) = accounts[..index]
+                .iter()
+                .find(|other| other.alias == account.alias || other.number == account.number)
+            {
+                problems.push(format!(
+                    "accounts: {:?} ({}) duplicates {:?} ({})",
+                    account.alias, account.number, other.alias, other.number
+                ));
+            }
+        }
+
+        if purpose == Purpose::Serve {
+            if let Err(err) = check_executable(&self.signal_cli_path) {
+                problems.push(format!(
+                    "signal_cli_path: {:#}; install signal-cli or point `signal_cli_path` at it",
+                    err
+                ));
+            }
+        }
+
+        if let Err(err) = check_storage_location(&self.storage) {
+            problems.push(format!("storage: {:#}", err));
+        }
+
+        // Only what the server writes is sealed; signal-cli's own attachment
+        // downloads stay plaintext (see `EncryptionSettings`).
+        if let Some(encryption) = &self.encryption {
+            match (&encryption.key_file, &encryption.passphrase_file) {
+                (Some(path), None) | (None, Some(path)) => {
+                    if let Err(err) = std::fs::File::open(path) {
+                        problems.push(format!(
+                            "encryption: cannot read {}: {}; the store stays locked without it",
+                            path.display(),
+                            err
+                        ));
+                    }
+                }
+                _ => problems.push(
+                    "encryption: set exactly one of `key_file` or `passphrase_file`".to_string(),
+                ),
+            }
+        }
+
+        if self.retention.interval_minutes == 0 {
+            problems.push("retention.interval_minutes: must be at least 1".to_string());
+        }
+        for (index, entry) in self.retention.conversations.iter().enumerate() {
+            if entry.conversation.trim().is_empty() {
+                problems.push(format!(
+                    "retention.conversations[{}]: `conversation` must not be empty",
+                    index
+                ));
+            } else if self.retention.conversations[..index]
+                .iter()
+                .any(|other| other.conversation == entry.conversation)
+            {
+                problems.push(format!(
+                    "retention.conversations: {:?} is listed more than once",
+                    entry.conversation
+                ));
+            }
+        }
+
+        if let Err(err) = EnvFilter::builder().parse(self.logging.directives()) {
+            problems.push(format!(
+                "logging: invalid `level` or `modules` entry: {}",
+                err
+            ));
+        }
+
+        if self.timeouts.default_seconds == 0 {
+            problems.push("timeouts.default_seconds: must be at least 1".to_string());
+        }
+        for (operation, seconds) in &self.timeouts.operations {
+            if *seconds == 0 {
+                problems.push(format!(
+                    "timeouts.operations.{}: must be at least 1",
+                    operation
+                ));
+            }
+        }
+
+        if self.transport == TransportKind::Http {
+            if self.http_host.trim().is_empty() {
+                problems
+                    .push("http_host: must not be empty with `transport = \"http\"`".to_string());
+            }
+            if self.http_port == 0 {
+                problems.push("http_port: must be between 1 and 65535".to_string());
+            }
+            let host = self.http_host.trim();
+            if parse_ip(host).is_some_and(|ip| ip.is_unspecified()) {
+                problems.push(format!(
+                    "http_host: {} listens on every address, so requests' Host header cannot be checked; bind a single address",
+                    host
+                ));
+            } else if !host.is_empty() && !is_loopback_host(host) && !self.http_allow_remote {
+                problems.push(format!(
+                    "http_host: {} is reachable from other machines and the HTTP endpoint has no authentication; bind 127.0.0.1 or set `http_allow_remote = true`",
+                    host
+                ));
+            }
+        }
+
+        if let Some(socket) = self
+            .daemon_socket
+            .as_ref()
+            .filter(|_| purpose == Purpose::Serve)
+        {
+            if let Err(err) = check_socket(socket) {
+                problems.push(format!(
+                    "daemon_socket: {:#}; start `signal-cli daemon --socket` or remove the setting",
+                    err
+                ));
+            }
+        }
+
+        problems
+    }
+
+    pub fn runtime_policy(&self) -> RuntimePolicy {
+        RuntimePolicy {
+            identity_policy: self.identity_policy,
+            retention: self.retention.clone(),
+        }
+    }
+
+    /// `Host` header values accepted over HTTP; others are refused to defeat DNS rebinding.
+    pub fn http_allowed_hosts(&self) -> Vec<String> {
+        let host = self.http_host.trim();
+        let mut names = vec![match parse_ip(host) {
+            Some(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => host.to_string(),
+        }];
+        if is_loopback_host(host) {
+            for alias in ["localhost", "127.0.0.1", "[::1]"] {
+                if !names.iter().any(|name| name.eq_ignore_ascii_case(alias)) {
+                    names.push(alias.to_string());
+                }
+            }
+        }
+        names
+            .into_iter()
+            .map(|name| format!("{}:{}", name, self.http_port))
+            .collect()
+    }
+
+    /// `Origin` header values accepted over HTTP, matching [`Self::http_allowed_hosts`].
+    pub fn http_allowed_origins(&self) -> Vec<String> {
+        self.http_allowed_hosts()
+            .into_iter()
+            .map(|host| format!("http://{}", host))
+            .collect()
+    }
+
+    /// Directory `file` log output rotates in.
+    pub fn log_directory(&self) -> PathBuf {
+        self.logging
+            .directory
+            .clone()
+            .unwrap_or_else(|| self.storage.join("logs"))
+    }
+
+    /// signal-cli's directory of downloaded attachments.
+    pub fn attachment_directory(&self) -> PathBuf {
+        self.attachments_dir.clone().unwrap_or_else(|| {
+            std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+                })
+                .unwrap_or_default()
+                .join("signal-cli")
+                .join("attachments")
+        })
+    }
+
+    /// Every served account, the default first.
+    ///
+    /// The top-level `account` is the default, served as `default` unless an
+    /// `[[accounts]]` entry names it; otherwise the first entry is the default.
+    pub fn all_accounts(&self) -> Vec<AccountSettings> {
+        let mut accounts = self.accounts.clone();
+        let primary = self.account.trim();
+        if !primary.is_empty() {
+            let entry = match accounts.iter().position(|entry| entry.number == primary) {
+                Some(index) => accounts.remove(index),
+                None => AccountSettings {
+                    alias: DEFAULT_ACCOUNT_ALIAS.to_string(),
+                    number: primary.to_string(),
+                },
+            };
+            accounts.insert(0, entry);
+        }
+        accounts
+    }
+
+    /// Load configuration for setup flows that run before an account exists.
+    ///
+    /// Reads `--config` when given, otherwise the first `config.toml` found in
+    /// the working directory, `$XDG_CONFIG_HOME/signal-mcp-server/` and each
+    /// `$XDG_CONFIG_DIRS` entry.
+    pub fn load_without_account(overrides: &Overrides) -> Result<Self> {
+        let config_path = overrides.config.clone().or_else(find_config_file);
+
+        let mut builder = Config::builder();
+        if let Some(path) = &config_path {
+            builder = builder.add_source(File::from(path.as_path()).required(true));
+        }
+        builder = builder.add_source(Environment::with_prefix("SIGNAL_MCP").separator("__"));
+
+        let cli_values = [
+            ("account", overrides.account.clone()),
+            (
+                "signal_cli_path",
+                overrides
+                    .signal_cli_path
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            ),
+            (
+                "storage",
+                overrides
+                    .storage
+                    .as_ref()
+                    .map(|path| path.display().to_string()),
+            ),
+            ("transport", overrides.transport.clone()),
+        ];
+        for (key, value) in cli_values {
+            if let Some(value) = value {
+                builder = builder
+                    .set_override(key, value)
+                    .map_err(map_config_error)
+                    .with_context(|| format!("invalid --{} override", key))?;
+            }
+        }
 
         let config = builder
             .build()
             .map_err(map_config_error)
             .context("failed to build configuration")?;
 
-        config
+        let mut settings = config
             .try_deserialize::<Settings>()
             .map_err(map_config_error)
-            .context("failed to deserialize configuration")
+            .context("failed to deserialize configuration")?;
+        settings.config_path = config_path;
+        Ok(settings)
+    }
+
+    /// Config file setup commands write to: the loaded one, or the user's XDG config file.
+    pub fn writable_config_path(&self) -> PathBuf {
+        self.config_path
+            .clone()
+            .or_else(|| config_home().map(|home| home.join(APP_DIR).join(CONFIG_FILE)))
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+    }
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_messages: None,
+            interval_minutes: default_retention_interval(),
+            conversations: Vec::new(),
+        }
+    }
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            outputs: default_log_outputs(),
+            format: LogFormat::default(),
+            level: default_log_level(),
+            modules: BTreeMap::new(),
+            directory: None,
+            rotation: LogRotation::default(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+impl Default for TimeoutSettings {
+    fn default() -> Self {
+        Self {
+            default_seconds: default_timeout_seconds(),
+            operations: BTreeMap::new(),
+        }
+    }
+}
+
+impl TimeoutSettings {
+    /// Time `operation` may take before it is killed.
+    pub fn limit(&self, operation: &str) -> Duration {
+        let seconds = self
+            .operations
+            .get(operation)
+            .copied()
+            .unwrap_or(self.default_seconds);
+        Duration::from_secs(seconds)
+    }
+}
+
+impl LoggingSettings {
+    /// `EnvFilter` directives: the default level, then each module's override.
+    pub fn directives(&self) -> String {
+        std::iter::once(self.level.clone())
+            .chain(
+                self.modules
+                    .iter()
+                    .map(|(module, level)| format!("{}={}", module, level)),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl RetentionSettings {
+    /// Whether any limit is configured at all.
+    pub fn is_enabled(&self) -> bool {
+        let limited = |days: Option<u32>, count: Option<usize>| {
+            days.is_some_and(|days| days > 0) || count.is_some_and(|count| count > 0)
+        };
+        limited(self.max_age_days, self.max_messages)
+            || self
+                .conversations
+                .iter()
+                .any(|entry| limited(entry.max_age_days, entry.max_messages))
+    }
+
+    /// Limits for `conversation`: its override where set, else the defaults.
+    pub fn rule_for(&self, conversation: &str) -> RetentionRule {
+        let entry = self
+            .conversations
+            .iter()
+            .find(|entry| entry.conversation == conversation);
+        RetentionRule {
+            max_age_days: entry
+                .and_then(|entry| entry.max_age_days)
+                .or(self.max_age_days)
+                .filter(|days| *days > 0),
+            max_messages: entry
+                .and_then(|entry| entry.max_messages)
+                .or(self.max_messages)
+                .filter(|count| *count > 0),
+        }
+    }
+}
+
+/// Record `account` in the config file at `path`, replacing any existing `account` entry.
+pub fn store_account(path: &Path, account: &str) -> Result<()> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+
+    let entry = format!("account = \"{}\"", account);
+    let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+    // Only keys before the first table header are top-level; `account` keys
+    // inside tables belong to them and are left alone.
+    let top_level = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing_entry = lines[..top_level].iter().position(|line| {
+        line.trim_start()
+            .strip_prefix("account")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    });
+    match existing_entry {
+        Some(index) => lines[index] = entry,
+        None => lines.insert(0, entry),
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    write_atomically(path, &contents)
+}
+
+/// Replace `path` with `contents` via a temporary file in the same directory,
+/// so an interrupted write never leaves a truncated config behind.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create {}", parent.display()))?;
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = parent.join(temp_name);
+
+    let written = (|| -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        // Keep the permissions of the file being replaced.
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("failed to write {}", path.display()));
+    }
+    Ok(())
+}
+
+fn find_config_file() -> Option<PathBuf> {
+    let mut candidates = vec![PathBuf::from(CONFIG_FILE)];
+    candidates.extend(config_home().map(|home| home.join(APP_DIR).join(CONFIG_FILE)));
+    let config_dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    candidates.extend(
+        config_dirs
+            .split(':')
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(|dir| Path::new(dir).join(APP_DIR).join(CONFIG_FILE)),
+    );
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+/// Fail unless `path` is an executable file.
+pub fn check_executable(path: &Path) -> Result<()> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("{} not found", path.display()))?;
+    if !metadata.is_file() {
+        anyhow::bail!("{} is not a file", path.display());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            anyhow::bail!("{} is not executable", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Check that `storage` is, or can become, a writable directory without creating it.
+fn check_storage_location(storage: &Path) -> Result<()> {
+    let existing = storage
+        .ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+        .unwrap_or(Path::new("."));
+    let metadata = std::fs::metadata(existing)
+        .with_context(|| format!("cannot inspect {}", existing.display()))?;
+    if !metadata.is_dir() {
+        anyhow::bail!("{} is not a directory", existing.display());
     }
+    if metadata.permissions().readonly() {
+        anyhow::bail!("{} is read-only", existing.display());
+    }
+    Ok(())
+}
+
+fn check_socket(socket: &Path) -> Result<()> {
+    let metadata =
+        std::fs::metadata(socket).with_context(|| format!("{} not found", socket.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} is not a socket", socket.display());
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    Ok(())
+}
+
+/// An IP address, with or without the brackets of an IPv6 URL host.
+fn parse_ip(host: &str) -> Option<IpAddr> {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+        .parse()
+        .ok()
+}
+
+fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost") || parse_ip(host).is_some_and(|ip| ip.is_loopback())
+}
+
+/// `+` followed by 7 to 15 digits, the first non-zero.
+fn is_e164(number: &str) -> bool {
+    number.strip_prefix('+').is_some_and(|digits| {
+        (7..=15).contains(&digits.len())
+            && digits.bytes().all(|b| b.is_ascii_digit())
+            && !digits.starts_with('0')
+    })
 }
 
 fn map_config_error(err: ConfigError) -> anyhow::Error {
@@ -42,6 +797,34 @@ fn default_signal_cli_path() -> PathBuf {
     PathBuf::from("/usr/bin/signal-cli")
 }
 
+fn default_http_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_http_port() -> u16 {
+    8080
+}
+
+fn default_retention_interval() -> u64 {
+    60
+}
+
+fn default_log_outputs() -> Vec<LogOutput> {
+    vec![LogOutput::Stderr]
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_timeout_seconds() -> u64 {
+    60
+}
+
 fn default_storage_directory() -> PathBuf {
     PathBuf::from("./var")
 }
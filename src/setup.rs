@@ -0,0 +1,155 @@
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Context, Result};
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tracing::info;
+
+use crate::settings::{self, Settings, TimeoutSettings};
+use crate::signal_cli::output_with_timeout;
+
+/// Link this installation as a secondary device and record the resulting account.
+///
+/// signal-cli prints the linking URI first and only exits once the primary
+/// device has scanned it, reporting `Associated with: <number>`.
+pub async fn link(settings: &Settings, device_name: &str) -> Result<()> {
+    let mut child = Command::new(&settings.signal_cli_path)
+        .arg("link")
+        .arg("-n")
+        .arg(device_name)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| "failed to execute signal-cli link")?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("signal-cli link stdout unavailable"))?;
+    let mut lines = BufReader::new(stdout).lines();
+    let mut account = None;
+
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .with_context(|| "failed to read signal-cli link output")?
+    {
+        let line = line.trim();
+        if line.starts_with("sgnl://") || line.starts_with("tsdevice:") {
+            println!("{}", render_qr(line)?);
+            println!("Scan the code above in Signal: Settings → Linked devices → Link new device.");
+            println!("Linking URI: {}", line);
+        } else if let Some(number) = line.strip_prefix("Associated with:") {
+            account = Some(number.trim().to_string());
+        } else if !line.is_empty() {
+            println!("{}", line);
+        }
+    }
+
+    let status = child
+        .wait()
+        .await
+        .with_context(|| "failed to wait for signal-cli link")?;
+    if !status.success() {
+        bail!("signal-cli link failed with {}", status);
+    }
+
+    let account =
+        account.ok_or_else(|| anyhow!("signal-cli link did not report the linked account"))?;
+    let config_path = settings.writable_config_path();
+    settings::store_account(&config_path, &account)?;
+    info!(%account, "device linked");
+    println!(
+        "Linked to {}; account written to {}",
+        account,
+        config_path.display()
+    );
+    Ok(())
+}
+
+/// Request a verification code for `number` by SMS or voice call.
+pub async fn register(
+    settings: &Settings,
+    number: &str,
+    voice: bool,
+    captcha: Option<&str>,
+) -> Result<()> {
+    let mut command = Command::new(&settings.signal_cli_path);
+    command.arg("--account").arg(number).arg("register");
+    if voice {
+        command.arg("--voice");
+    }
+    if let Some(captcha) = captcha {
+        command.arg("--captcha").arg(captcha);
+    }
+
+    run_interactive(command, "register", &settings.timeouts).await?;
+    let channel = if voice { "voice call" } else { "SMS" };
+    println!(
+        "Verification code requested by {}. Complete setup with `signal-mcp-server verify {} <code>`.",
+        channel, number
+    );
+    Ok(())
+}
+
+/// Finish registration with the received code and record the account.
+pub async fn verify(
+    settings: &Settings,
+    number: &str,
+    code: &str,
+    pin: Option<&str>,
+) -> Result<()> {
+    let mut command = Command::new(&settings.signal_cli_path);
+    command.arg("--account").arg(number).arg("verify").arg(code);
+    if let Some(pin) = pin {
+        command.arg("--pin").arg(pin);
+    }
+
+    run_interactive(command, "verify", &settings.timeouts).await?;
+    let config_path = settings.writable_config_path();
+    settings::store_account(&config_path, number)?;
+    info!(account = %number, "account verified");
+    println!(
+        "Verified {}; account written to {}",
+        number,
+        config_path.display()
+    );
+    Ok(())
+}
+
+async fn run_interactive(
+    mut command: Command,
+    operation: &str,
+    timeouts: &TimeoutSettings,
+) -> Result<()> {
+    let output = output_with_timeout(&mut command, operation, timeouts.limit(operation)).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let hint = if stderr.to_ascii_lowercase().contains("captcha") {
+            "\nSolve a captcha at https://signalcaptchas.org/registration/generate.html and pass the resulting `signalcaptcha://` token with --captcha."
+        } else {
+            ""
+        };
+        bail!("signal-cli {} failed: {}{}", operation, stderr.trim(), hint);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        println!("{}", stdout.trim());
+    }
+    Ok(())
+}
+
+fn render_qr(uri: &str) -> Result<String> {
+    let code = QrCode::new(uri.as_bytes()).with_context(|| "failed to encode linking URI")?;
+    Ok(code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
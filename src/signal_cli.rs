@@ -1,19 +1,84 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
 use serde_json::Value;
-use std::process::Stdio;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::process::{Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::Command;
-use tracing::debug;
+use tokio::sync::watch;
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::mcp::{Participant, TextStyle};
+use crate::settings::{IdentityPolicy, RuntimePolicy, TimeoutSettings};
+use crate::text::{style_cli_arg, MentionSpan};
 
 #[derive(Debug, Clone)]
 pub struct SignalCli {
     pub executable: std::path::PathBuf,
     pub account: String,
+    policy: watch::Receiver<RuntimePolicy>,
+    timeouts: TimeoutSettings,
+    /// Output of timed-out `receive` runs, handed out by the next [`SignalCli::receive`].
+    late_receive: LateOutput,
+}
+
+/// Stdout a timed-out process produced after its caller gave up on it.
+type LateOutput = Arc<Mutex<Vec<u8>>>;
+
+/// Failures callers may want to react to rather than just relay.
+#[derive(Debug, thiserror::Error)]
+pub enum SignalCliError {
+    #[error("untrusted identity for {}: {stderr}", addresses.join(", "))]
+    UntrustedIdentity {
+        addresses: Vec<String>,
+        stderr: String,
+    },
+    #[error("signal-cli {operation} did not finish within {seconds}s and {}", abandoned(*killed))]
+    TimedOut {
+        operation: String,
+        seconds: u64,
+        killed: bool,
+        stderr: String,
+    },
+    #[error("signal-cli {operation} waited {seconds}s for the account's data, which another signal-cli instance (such as a running daemon) has locked, and {}", abandoned(*killed))]
+    Locked {
+        operation: String,
+        seconds: u64,
+        killed: bool,
+    },
+}
+
+/// How a timed-out operation was left, for [`SignalCliError`] messages.
+fn abandoned(killed: bool) -> String {
+    if killed {
+        "was killed".to_string()
+    } else {
+        format!(
+            "was left running for at most {} more minutes; it may still have completed, so check before retrying",
+            DETACHED_LIMIT.as_secs() / 60
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ChatEntry {
     pub id: String,
     pub name: Option<String>,
+    pub kind: ChatKind,
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatKind {
+    Contact,
+    Group,
 }
 
 impl SignalCli {
@@ -21,33 +86,29 @@ impl SignalCli {
         Self {
             executable,
             account,
+            policy: watch::channel(RuntimePolicy::default()).1,
+            timeouts: TimeoutSettings::default(),
+            late_receive: LateOutput::default(),
         }
     }
 
+    /// Kill operations that run longer than `timeouts` allows.
+    pub fn with_timeouts(mut self, timeouts: TimeoutSettings) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Follow `policy`, which the config reloader updates in place.
+    pub fn with_policy(mut self, policy: watch::Receiver<RuntimePolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
     pub async fn list_chats(&self) -> Result<Vec<ChatEntry>> {
         let mut chats = Vec::new();
 
-        let contacts_output = Command::new(&self.executable)
-            .arg("--account")
-            .arg(&self.account)
-            .arg("-o")
-            .arg("json")
-            .arg("listContacts")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .with_context(|| "failed to execute signal-cli listContacts")?;
-
-        if !contacts_output.status.success() {
-            let stderr = String::from_utf8_lossy(&contacts_output.stderr);
-            return Err(anyhow::anyhow!(
-                "signal-cli listContacts failed: {}",
-                stderr.trim()
-            ));
-        }
-
-        let contacts: Vec<Value> = serde_json::from_slice(&contacts_output.stdout)
+        let contacts_output = self.run_json("listContacts", NO_ARGS).await?;
+        let contacts: Vec<Value> = serde_json::from_slice(&contacts_output)
             .with_context(|| "failed to parse signal-cli listContacts response")?;
 
         for contact in contacts {
@@ -61,31 +122,14 @@ impl SignalCli {
                 chats.push(ChatEntry {
                     id: number.to_string(),
                     name,
+                    kind: ChatKind::Contact,
+                    blocked: is_blocked(&contact),
                 });
             }
         }
 
-        let groups_output = Command::new(&self.executable)
-            .arg("--account")
-            .arg(&self.account)
-            .arg("-o")
-            .arg("json")
-            .arg("listGroups")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .with_context(|| "failed to execute signal-cli listGroups")?;
-
-        if !groups_output.status.success() {
-            let stderr = String::from_utf8_lossy(&groups_output.stderr);
-            return Err(anyhow::anyhow!(
-                "signal-cli listGroups failed: {}",
-                stderr.trim()
-            ));
-        }
-
-        let groups: Vec<Value> = serde_json::from_slice(&groups_output.stdout)
+        let groups_output = self.run_json("listGroups", NO_ARGS).await?;
+        let groups: Vec<Value> = serde_json::from_slice(&groups_output)
             .with_context(|| "failed to parse signal-cli listGroups response")?;
 
         for group in groups {
@@ -101,6 +145,8 @@ impl SignalCli {
                 chats.push(ChatEntry {
                     id: id.to_string(),
                     name: Some(display_name),
+                    kind: ChatKind::Group,
+                    blocked: is_blocked(&group),
                 });
             }
         }
@@ -109,27 +155,770 @@ impl SignalCli {
         Ok(chats)
     }
 
-    pub async fn send_message(&self, recipient: &str, message: &str) -> Result<String> {
-        let output = Command::new(&self.executable)
+    pub async fn send_message(
+        &self,
+        recipient: &str,
+        message: &str,
+        mentions: &[MentionSpan],
+        styles: &[TextStyle],
+    ) -> Result<String> {
+        validate_recipient(recipient)?;
+        let mut args: Vec<OsString> = vec!["-m".into(), message.into()];
+        for mention in mentions {
+            args.push("--mention".into());
+            args.push(mention.to_cli_arg().into());
+        }
+        for style in styles {
+            args.push("--text-style".into());
+            args.push(style_cli_arg(style).into());
+        }
+        if is_group_recipient(recipient) {
+            args.push("-g".into());
+        }
+        args.push(recipient.into());
+
+        let output = self.run("send", args).await?;
+        let response = String::from_utf8_lossy(&output).trim().to_string();
+        debug!(
+            ?recipient,
+            mentions = mentions.len(),
+            "signal-cli send succeeded"
+        );
+        Ok(response)
+    }
+
+    /// Drain pending messages, returning the raw `envelope` objects signal-cli emits.
+    ///
+    /// Envelopes an earlier, timed-out run printed after it was given up on
+    /// come first; signal-cli has acknowledged them, so they are never dropped.
+    pub async fn receive(&self, timeout_seconds: u64) -> Result<Vec<Value>> {
+        // signal-cli itself waits up to `timeout_seconds`, on top of the usual limit.
+        let output = self
+            .execute(
+                "receive",
+                true,
+                ["--timeout".to_string(), timeout_seconds.to_string()],
+                Duration::from_secs(timeout_seconds),
+            )
+            .await?;
+        let late =
+            std::mem::take(&mut *self.late_receive.lock().expect("late output lock poisoned"));
+
+        let mut envelopes = Vec::new();
+        for line in String::from_utf8_lossy(&late).lines() {
+            // A run killed at the hard limit may have stopped mid-line.
+            match parse_receive_line(line) {
+                Ok(envelope) => envelopes.extend(envelope),
+                Err(err) => warn!(?err, "skipping unreadable output of a timed-out receive"),
+            }
+        }
+        for line in String::from_utf8_lossy(&output).lines() {
+            envelopes.extend(parse_receive_line(line)?);
+        }
+
+        debug!(count = envelopes.len(), "signal-cli envelopes received");
+        Ok(envelopes)
+    }
+
+    /// Apply `update` to a contact's local address book entry.
+    pub async fn update_contact(&self, recipient: &str, update: &ContactUpdate) -> Result<()> {
+        let mut args: Vec<OsString> = vec![recipient.into()];
+        for (flag, value) in [
+            ("--given-name", &update.given_name),
+            ("--family-name", &update.family_name),
+            ("--nick-given-name", &update.nick_given_name),
+            ("--nick-family-name", &update.nick_family_name),
+            ("--note", &update.note),
+        ] {
+            if let Some(value) = value {
+                args.push(flag.into());
+                args.push(value.into());
+            }
+        }
+        if let Some(seconds) = update.expiration_seconds {
+            args.push("-e".into());
+            args.push(seconds.to_string().into());
+        }
+
+        self.run("updateContact", args).await?;
+        debug!(?recipient, "signal-cli updateContact succeeded");
+        Ok(())
+    }
+
+    /// Remove a contact. Without `forget` the contact is only hidden from the address book.
+    pub async fn remove_contact(&self, recipient: &str, forget: bool) -> Result<()> {
+        let flag = if forget { "--forget" } else { "--hide" };
+        self.run("removeContact", [flag, recipient]).await?;
+        debug!(?recipient, forget, "signal-cli removeContact succeeded");
+        Ok(())
+    }
+
+    /// Block or unblock contacts and groups in one call.
+    pub async fn set_blocked(&self, recipients: &[String], blocked: bool) -> Result<()> {
+        let operation = if blocked { "block" } else { "unblock" };
+        for recipient in recipients {
+            validate_recipient(recipient)?;
+        }
+        let (groups, contacts): (Vec<String>, Vec<String>) = recipients
+            .iter()
+            .cloned()
+            .partition(|recipient| is_group_recipient(recipient));
+        let mut args: Vec<OsString> = contacts.iter().map(OsString::from).collect();
+        push_list(&mut args, "-g", &groups);
+
+        self.run(operation, args).await?;
+        debug!(
+            count = recipients.len(),
+            blocked, "signal-cli block state updated"
+        );
+        Ok(())
+    }
+
+    /// List known identity keys, optionally restricted to one contact.
+    pub async fn list_identities(&self, recipient: Option<&str>) -> Result<Vec<IdentityEntry>> {
+        let mut args: Vec<&str> = Vec::new();
+        if let Some(recipient) = recipient {
+            args.push("-n");
+            args.push(recipient);
+        }
+
+        let output = self.run_json("listIdentities", args).await?;
+        let identities: Vec<Value> = serde_json::from_slice(&output)
+            .with_context(|| "failed to parse signal-cli listIdentities response")?;
+
+        let text = |entry: &Value, key: &str| {
+            entry
+                .get(key)
+                .and_then(Value::as_str)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+        };
+        let entries = identities
+            .iter()
+            .filter_map(|entry| {
+                Some(IdentityEntry {
+                    address: text(entry, "number").or_else(|| text(entry, "uuid"))?,
+                    safety_number: text(entry, "safetyNumber"),
+                    fingerprint: text(entry, "fingerprint"),
+                    trust_level: text(entry, "trustLevel"),
+                    added: entry
+                        .get("addedTimestamp")
+                        .and_then(Value::as_i64)
+                        .and_then(DateTime::<Utc>::from_timestamp_millis),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Trust a contact's identity key, verified against `safety_number` when given.
+    ///
+    /// Without a safety number every known key for the contact is trusted.
+    pub async fn trust_identity(&self, recipient: &str, safety_number: Option<&str>) -> Result<()> {
+        let mut args = vec![recipient];
+        match safety_number {
+            Some(safety_number) => {
+                args.push("-v");
+                args.push(safety_number);
+            }
+            None => args.push("-a"),
+        }
+
+        self.run("trust", args).await?;
+        debug!(
+            ?recipient,
+            verified = safety_number.is_some(),
+            "signal-cli trust succeeded"
+        );
+        Ok(())
+    }
+
+    /// List the devices linked to this account, including this one.
+    pub async fn list_devices(&self) -> Result<Vec<DeviceEntry>> {
+        let output = self.run_json("listDevices", NO_ARGS).await?;
+        let devices: Vec<Value> = serde_json::from_slice(&output)
+            .with_context(|| "failed to parse signal-cli listDevices response")?;
+
+        let timestamp = |entry: &Value, key: &str| {
+            entry
+                .get(key)
+                .and_then(Value::as_i64)
+                .and_then(DateTime::<Utc>::from_timestamp_millis)
+        };
+        let entries = devices
+            .iter()
+            .filter_map(|entry| {
+                Some(DeviceEntry {
+                    id: entry.get("id").and_then(Value::as_u64)?,
+                    name: entry
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string()),
+                    created: timestamp(entry, "createdTimestamp"),
+                    last_seen: timestamp(entry, "lastSeenTimestamp"),
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Link a new device from the `sgnl://linkdevice` URI it displays.
+    pub async fn add_device(&self, uri: &str) -> Result<()> {
+        self.run("addDevice", ["--uri", uri]).await?;
+        debug!("signal-cli addDevice succeeded");
+        Ok(())
+    }
+
+    pub async fn remove_device(&self, device_id: u64) -> Result<()> {
+        self.run("removeDevice", ["-d", &device_id.to_string()])
+            .await?;
+        debug!(device_id, "signal-cli removeDevice succeeded");
+        Ok(())
+    }
+
+    /// Fetch the detailed view of a single group from `listGroups -d`.
+    pub async fn get_group(&self, group_id: &str) -> Result<GroupDetails> {
+        let output = self.run_json("listGroups", ["-d", "-g", group_id]).await?;
+        let groups: Vec<Value> = serde_json::from_slice(&output)
+            .with_context(|| "failed to parse signal-cli listGroups response")?;
+
+        let group = groups
+            .iter()
+            .find(|group| group.get("id").and_then(Value::as_str) == Some(group_id))
+            .ok_or_else(|| anyhow::anyhow!("group {} is not known to this account", group_id))?;
+
+        Ok(GroupDetails::from_json(group))
+    }
+
+    /// Create a group (when `group_id` is `None`) or apply `update` to an existing one.
+    ///
+    /// Returns the ID of the affected group.
+    pub async fn update_group(
+        &self,
+        group_id: Option<&str>,
+        update: &GroupUpdate,
+    ) -> Result<String> {
+        let mut args: Vec<OsString> = Vec::new();
+        if let Some(group_id) = group_id {
+            args.push("-g".into());
+            args.push(group_id.into());
+        }
+        if let Some(name) = &update.name {
+            args.push("-n".into());
+            args.push(name.into());
+        }
+        if let Some(description) = &update.description {
+            args.push("-d".into());
+            args.push(description.into());
+        }
+        if let Some(avatar) = &update.avatar {
+            args.push("-a".into());
+            args.push(avatar.into());
+        }
+        push_list(&mut args, "-m", &update.add_members);
+        push_list(&mut args, "-r", &update.remove_members);
+        push_list(&mut args, "--admin", &update.add_admins);
+        push_list(&mut args, "--remove-admin", &update.remove_admins);
+        if let Some(seconds) = update.expiration_seconds {
+            args.push("-e".into());
+            args.push(seconds.to_string().into());
+        }
+        if update.reset_invite_link {
+            args.push("--reset-link".into());
+        }
+
+        let output = self.run_json("updateGroup", args).await?;
+        let created = serde_json::from_slice::<Value>(&output)
+            .ok()
+            .and_then(|value| {
+                value
+                    .get("groupId")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            });
+
+        let group_id = match (group_id, created) {
+            (Some(group_id), _) => group_id.to_string(),
+            (None, Some(created)) => created,
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "signal-cli updateGroup did not report the new group ID"
+                ))
+            }
+        };
+        debug!(%group_id, "signal-cli updateGroup succeeded");
+        Ok(group_id)
+    }
+
+    /// Leave a group, optionally deleting its local data as well.
+    pub async fn quit_group(&self, group_id: &str, delete: bool) -> Result<()> {
+        let mut args: Vec<OsString> = vec!["-g".into(), group_id.into()];
+        if delete {
+            args.push("--delete".into());
+        }
+        self.run("quitGroup", args).await?;
+        debug!(%group_id, delete, "signal-cli quitGroup succeeded");
+        Ok(())
+    }
+
+    async fn run_json<I, S>(&self, operation: &str, args: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.execute(operation, true, args, Duration::ZERO).await
+    }
+
+    async fn run<I, S>(&self, operation: &str, args: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.execute(operation, false, args, Duration::ZERO).await
+    }
+
+    /// Run `signal-cli --account <account> [-o json] <operation> <args>` and return stdout.
+    ///
+    /// Gives up once the operation's configured timeout plus `allowance` has
+    /// elapsed; see [`output_with_timeout`] for what happens to the process.
+    async fn execute<I, S>(
+        &self,
+        operation: &str,
+        json: bool,
+        args: I,
+        allowance: Duration,
+    ) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(&self.executable);
+        command
             .arg("--account")
             .arg(&self.account)
-            .arg("send")
-            .arg("-m")
-            .arg(message)
-            .arg(recipient)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .with_context(|| "failed to execute signal-cli send")?;
+            .arg("--trust-new-identities")
+            .arg(trust_new_identities_mode(
+                self.policy.borrow().identity_policy,
+            ));
+        if json {
+            command.arg("-o").arg("json");
+        }
 
+        command
+            .arg(operation)
+            .args(args)
+            .kill_on_drop(ABORTABLE_OPERATIONS.contains(&operation));
+        let limit = self.timeouts.limit(operation) + allowance;
+        let late = (operation == "receive").then_some(&self.late_receive);
+        let output = run_with_timeout(&mut command, operation, limit, late).await?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if !stderr.is_empty() {
+            debug!(operation, %stderr, "signal-cli stderr");
+        }
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("signal-cli send failed: {}", stderr.trim()));
+            if stderr.to_ascii_lowercase().contains("untrusted identit") {
+                return Err(SignalCliError::UntrustedIdentity {
+                    addresses: extract_addresses(&stderr),
+                    stderr,
+                }
+                .into());
+            }
+            return Err(anyhow::anyhow!(
+                "signal-cli {} failed: {}",
+                operation,
+                stderr
+            ));
         }
 
-        let response = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        debug!(?recipient, "signal-cli send succeeded");
-        Ok(response)
+        Ok(output.stdout)
+    }
+}
+
+/// An identity key entry from `listIdentities`.
+#[derive(Debug, Clone)]
+pub struct IdentityEntry {
+    pub address: String,
+    pub safety_number: Option<String>,
+    pub fingerprint: Option<String>,
+    pub trust_level: Option<String>,
+    pub added: Option<DateTime<Utc>>,
+}
+
+/// A linked device entry from `listDevices`.
+#[derive(Debug, Clone)]
+pub struct DeviceEntry {
+    pub id: u64,
+    pub name: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Changes applied by `updateContact`; unset fields are left untouched.
+#[derive(Debug, Clone, Default)]
+pub struct ContactUpdate {
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub nick_given_name: Option<String>,
+    pub nick_family_name: Option<String>,
+    pub note: Option<String>,
+    pub expiration_seconds: Option<u32>,
+}
+
+/// Changes applied by `updateGroup`; unset fields are left untouched.
+#[derive(Debug, Clone, Default)]
+pub struct GroupUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub avatar: Option<PathBuf>,
+    pub add_members: Vec<String>,
+    pub remove_members: Vec<String>,
+    pub add_admins: Vec<String>,
+    pub remove_admins: Vec<String>,
+    pub expiration_seconds: Option<u32>,
+    pub reset_invite_link: bool,
+}
+
+/// Group metadata as reported by `listGroups -d`.
+#[derive(Debug, Clone)]
+pub struct GroupDetails {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub is_member: bool,
+    pub is_blocked: bool,
+    pub expiration_seconds: u64,
+    pub members: Vec<Participant>,
+    pub pending_members: Vec<Participant>,
+    pub requesting_members: Vec<Participant>,
+    pub admins: Vec<Participant>,
+    pub invite_link: Option<String>,
+    pub permission_add_member: Option<String>,
+    pub permission_edit_details: Option<String>,
+    pub permission_send_message: Option<String>,
+}
+
+impl GroupDetails {
+    fn from_json(group: &Value) -> Self {
+        let text = |key: &str| {
+            group
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+        };
+
+        Self {
+            id: text("id").unwrap_or_default(),
+            name: text("name"),
+            description: text("description"),
+            is_member: group
+                .get("isMember")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            is_blocked: is_blocked(group),
+            expiration_seconds: group
+                .get("messageExpirationTime")
+                .and_then(Value::as_u64)
+                .unwrap_or(0),
+            members: parse_member_list(group.get("members")),
+            pending_members: parse_member_list(group.get("pendingMembers")),
+            requesting_members: parse_member_list(group.get("requestingMembers")),
+            admins: parse_member_list(group.get("admins")),
+            invite_link: text("groupInviteLink"),
+            permission_add_member: text("permissionAddMember"),
+            permission_edit_details: text("permissionEditDetails"),
+            permission_send_message: text("permissionSendMessage"),
+        }
+    }
+}
+
+/// signal-cli lists members either as bare numbers or as `{number, uuid}` objects.
+fn parse_member_list(value: Option<&Value>) -> Vec<Participant> {
+    value
+        .and_then(Value::as_array)
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    let address = match entry {
+                        Value::String(address) => Some(address.as_str()),
+                        _ => entry
+                            .get("number")
+                            .and_then(Value::as_str)
+                            .or_else(|| entry.get("uuid").and_then(Value::as_str)),
+                    }?;
+                    Some(Participant {
+                        address: address.to_string(),
+                        display_name: None,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+const NO_ARGS: [&str; 0] = [];
+
+/// Length of a decoded GroupsV2 group ID.
+const GROUP_ID_LEN: usize = 32;
+
+/// Operations that are killed when they time out or their caller goes away,
+/// e.g. a cancelled tool call. Anything else changes state on the Signal
+/// servers, so once started it runs to completion rather than being cut off
+/// halfway.
+const ABORTABLE_OPERATIONS: [&str; 6] = [
+    "--version",
+    "listAccounts",
+    "listContacts",
+    "listGroups",
+    "listIdentities",
+    "listDevices",
+];
+
+/// How much longer a timed-out operation left running may take before it is killed after all.
+const DETACHED_LIMIT: Duration = Duration::from_secs(10 * 60);
+
+/// Run `command` to completion with piped output, giving up once `limit`
+/// elapses. [`ABORTABLE_OPERATIONS`], and anything still waiting for another
+/// instance's lock, are killed then; anything else is left running for up to
+/// [`DETACHED_LIMIT`], as it may already have taken effect. Stderr is watched
+/// as it arrives so a process stuck behind another instance's lock is
+/// reported as such.
+pub async fn output_with_timeout(
+    command: &mut Command,
+    operation: &str,
+    limit: Duration,
+) -> Result<Output> {
+    run_with_timeout(command, operation, limit, None).await
+}
+
+/// [`output_with_timeout`], collecting all stdout of a process left running
+/// into `late`, including what it printed before the timeout.
+async fn run_with_timeout(
+    command: &mut Command,
+    operation: &str,
+    limit: Duration,
+    late: Option<&LateOutput>,
+) -> Result<Output> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to execute signal-cli {}", operation))?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .context("signal-cli stdout not captured")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("signal-cli stderr not captured")?;
+
+    let mut stdout_bytes = Vec::new();
+    let mut stderr_lines = Vec::new();
+    let mut locked = false;
+    let mut lines = BufReader::new(stderr).lines();
+    let finished = timeout(limit, async {
+        let read_stderr = async {
+            while let Some(line) = lines.next_line().await? {
+                if !locked && is_lock_contention(&line) {
+                    locked = true;
+                    warn!(
+                        operation,
+                        "signal-cli is waiting for a lock held by another signal-cli instance"
+                    );
+                }
+                stderr_lines.push(line);
+            }
+            Ok(())
+        };
+        let (status, (), _) = tokio::try_join!(
+            child.wait(),
+            read_stderr,
+            stdout.read_to_end(&mut stdout_bytes)
+        )?;
+        Ok::<_, std::io::Error>(status)
+    })
+    .await;
+
+    let stderr = stderr_lines.join("\n");
+    let Ok(status) = finished else {
+        // A process still waiting for the lock has not done anything yet.
+        let killed = locked || ABORTABLE_OPERATIONS.contains(&operation);
+        let operation = operation.to_string();
+        if killed {
+            if let Err(err) = child.kill().await {
+                warn!(?err, %operation, "failed to kill timed out signal-cli");
+            }
+        } else {
+            // Keep draining its output so the process is not cut off by a closed pipe.
+            let operation = operation.clone();
+            let late = late.cloned();
+            tokio::spawn(async move {
+                let finished = timeout(DETACHED_LIMIT, async {
+                    let read_stderr = async { while let Ok(Some(_)) = lines.next_line().await {} };
+                    let (status, (), _) = tokio::join!(
+                        child.wait(),
+                        read_stderr,
+                        stdout.read_to_end(&mut stdout_bytes)
+                    );
+                    status
+                })
+                .await;
+                match finished {
+                    Ok(Ok(status)) => info!(%operation, %status, "timed out signal-cli finished"),
+                    Ok(Err(err)) => {
+                        warn!(?err, %operation, "failed to wait for timed out signal-cli")
+                    }
+                    Err(_) => {
+                        warn!(%operation, "timed out signal-cli is still running; killing it");
+                        if let Err(err) = child.kill().await {
+                            warn!(?err, %operation, "failed to kill timed out signal-cli");
+                        }
+                    }
+                }
+                if let Some(late) = late {
+                    late.lock()
+                        .expect("late output lock poisoned")
+                        .extend_from_slice(&stdout_bytes);
+                }
+            });
+        }
+        let seconds = limit.as_secs();
+        warn!(%operation, seconds, locked, killed, %stderr, "signal-cli timed out");
+        return Err(if locked {
+            SignalCliError::Locked {
+                operation,
+                seconds,
+                killed,
+            }
+        } else {
+            SignalCliError::TimedOut {
+                operation,
+                seconds,
+                killed,
+                stderr,
+            }
+        }
+        .into());
+    };
+    let status = status.with_context(|| format!("failed to run signal-cli {}", operation))?;
+    Ok(Output {
+        status,
+        stdout: stdout_bytes,
+        stderr: stderr.into_bytes(),
+    })
+}
+
+/// The `envelope` of one line of `receive` output, if it carries one.
+fn parse_receive_line(line: &str) -> Result<Option<Value>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let mut value: Value = serde_json::from_str(line)
+        .with_context(|| "failed to parse signal-cli receive response")?;
+    Ok(value.get_mut("envelope").map(Value::take))
+}
+
+/// signal-cli logs this while it waits for another process to release the account's data.
+fn is_lock_contention(line: &str) -> bool {
+    line.to_ascii_lowercase()
+        .contains("in use by another instance")
+}
+
+fn trust_new_identities_mode(policy: IdentityPolicy) -> &'static str {
+    match policy {
+        IdentityPolicy::Block => "never",
+        // `warn` re-trusts changed keys itself so the change can be reported.
+        IdentityPolicy::Warn | IdentityPolicy::TrustOnFirstUse => "on-first-use",
+    }
+}
+
+/// Pull the E.164 numbers and service UUIDs out of signal-cli's untrusted-identity diagnostics.
+///
+/// Contacts without a known number are reported by UUID alone.
+fn extract_addresses(stderr: &str) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    let tokens = stderr.split(|c: char| !(c.is_ascii_alphanumeric() || c == '+' || c == '-'));
+    for token in tokens {
+        let address = match token.strip_prefix('+') {
+            Some(digits) => (digits.len() >= 6 && digits.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| token.to_string()),
+            // Only the hyphenated form, so other hex strings are not mistaken for UUIDs.
+            None if token.len() == 36 => Uuid::try_parse(token).ok().map(|uuid| uuid.to_string()),
+            None => None,
+        };
+        if let Some(address) = address {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    addresses
+}
+
+fn is_blocked(entry: &Value) -> bool {
+    entry
+        .get("isBlocked")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn push_list(args: &mut Vec<OsString>, flag: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    args.push(flag.into());
+    args.extend(values.iter().map(OsString::from));
+}
+
+/// Group IDs are the base64 encoding of a 32-byte GroupsV2 identifier.
+pub fn is_group_recipient(recipient: &str) -> bool {
+    BASE64
+        .decode(recipient)
+        .is_ok_and(|bytes| bytes.len() == GROUP_ID_LEN)
+}
+
+/// Reject anything that is neither a group ID nor an individual address: a
+/// `+` number, a `u:` username, or an ACI or `PNI:` UUID.
+pub fn validate_recipient(recipient: &str) -> Result<()> {
+    let number = recipient
+        .strip_prefix('+')
+        .is_some_and(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()));
+    let username = recipient
+        .strip_prefix("u:")
+        .is_some_and(|name| !name.is_empty());
+    let uuid = Uuid::parse_str(recipient.strip_prefix("PNI:").unwrap_or(recipient)).is_ok();
+    if number || username || uuid || is_group_recipient(recipient) {
+        return Ok(());
+    }
+    bail!(
+        "{:?} is not a recipient: expected a +E.164 number, u:username, UUID or base64 group ID",
+        recipient
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_addresses;
+
+    #[test]
+    fn untrusted_identities_are_found_by_number_and_uuid() {
+        let stderr = "Failed to send message: Untrusted identity for \"+15551234567\"\n\
+            Untrusted identity for \"4C1F2A7E-9B3D-4E5F-8A6B-1C2D3E4F5A6B\"\n\
+            +15551234567: Untrusted Identity (key 0123456789abcdef0123456789abcdef, +12)";
+        assert_eq!(
+            extract_addresses(stderr),
+            ["+15551234567", "4c1f2a7e-9b3d-4e5f-8a6b-1c2d3e4f5a6b"]
+        );
+    }
+
+    #[test]
+    fn diagnostics_without_addresses_yield_none() {
+        assert!(extract_addresses("Untrusted identity for a group member").is_empty());
     }
 }
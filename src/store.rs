@@ -0,0 +1,991 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::crypto::StoreKey;
+use crate::mcp::{AttachmentId, ConversationId, Message};
+
+/// Sealed list of the conversations in an encrypted store, one ID per line.
+const INDEX_FILE: &str = "index";
+/// Index being built by [`Store::seal_legacy_files`]; renamed to [`INDEX_FILE`] once done.
+const PARTIAL_INDEX_FILE: &str = "index.partial";
+/// Held locked by the process that has an account's store open.
+const LOCK_FILE: &str = ".lock";
+
+/// Append-only, per-conversation event log kept under `Settings::storage`.
+///
+/// Every account has its own log under `accounts/<number>/`. Each
+/// conversation is a JSON Lines file named after the hex-encoded conversation
+/// ID, so group IDs containing `/` or `=` map to safe file names. With a
+/// [`StoreKey`] every line is sealed individually instead of stored as JSON,
+/// files are named by a keyed hash of the ID instead, and a sealed index maps
+/// the names back to conversations.
+#[derive(Debug)]
+pub struct Store {
+    conversations_dir: PathBuf,
+    key: Option<Arc<StoreKey>>,
+    write_lock: Mutex<()>,
+    /// Earliest disappearing-message deadline per conversation still on disk.
+    expiries: std::sync::Mutex<HashMap<ConversationId, DateTime<Utc>>>,
+    /// Exclusive lock on the account directory, released when the store is dropped.
+    _lock: std::fs::File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoredEvent {
+    Message(Message),
+    IdentityChange(IdentityChange),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityChange {
+    pub address: String,
+    pub detected_at: DateTime<Utc>,
+    pub action: IdentityAction,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityAction {
+    /// A send was refused because the contact's identity key is untrusted.
+    SendBlocked,
+    /// The new identity key was trusted automatically under the `warn` policy.
+    AutoTrusted,
+    /// An operator trusted the identity key through a tool call.
+    Trusted,
+}
+
+/// What a retention pass or purge removed from one conversation.
+#[derive(Debug, Clone, Default)]
+pub struct Pruned {
+    pub events: usize,
+    /// Attachments referenced by removed messages.
+    pub attachments: Vec<AttachmentId>,
+}
+
+impl StoredEvent {
+    fn occurred_at(&self) -> DateTime<Utc> {
+        match self {
+            StoredEvent::Message(message) => message.timestamp,
+            StoredEvent::IdentityChange(change) => change.detected_at,
+        }
+    }
+
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            StoredEvent::Message(message) => message.expires_at(),
+            StoredEvent::IdentityChange(_) => None,
+        }
+    }
+
+    fn expired_at(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+
+    fn attachments(&self) -> Vec<AttachmentId> {
+        match self {
+            StoredEvent::Message(message) => message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.id.clone())
+                .collect(),
+            StoredEvent::IdentityChange(_) => Vec::new(),
+        }
+    }
+}
+
+impl Store {
+    /// Open `account`'s store, failing if another process already has it open.
+    pub async fn open_account(
+        storage: &Path,
+        account: &str,
+        key: Option<Arc<StoreKey>>,
+    ) -> Result<Self> {
+        let conversations_dir = account_root(storage, account).join("conversations");
+        fs::create_dir_all(&conversations_dir)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to create store directory {}",
+                    conversations_dir.display()
+                )
+            })?;
+
+        let lock = lock_account(&account_root(storage, account), account)?;
+
+        let store = Self {
+            conversations_dir,
+            key,
+            write_lock: Mutex::new(()),
+            expiries: std::sync::Mutex::new(HashMap::new()),
+            _lock: lock,
+        };
+        if store.key.is_some()
+            && !fs::try_exists(store.conversations_dir.join(INDEX_FILE))
+                .await
+                .unwrap_or(false)
+        {
+            store.seal_legacy_files().await?;
+        }
+        for conversation in store.conversations().await? {
+            let events = store.read_events(&conversation).await?;
+            store.track_expiry(&conversation, &events, true);
+        }
+        Ok(store)
+    }
+
+    /// Move a store written before accounts were namespaced into `account`'s directory.
+    pub async fn adopt_legacy_layout(storage: &Path, account: &str) -> Result<()> {
+        let legacy = storage.join("conversations");
+        let target = account_root(storage, account);
+        if !fs::try_exists(&legacy).await.unwrap_or(false)
+            || fs::try_exists(target.join("conversations"))
+                .await
+                .unwrap_or(false)
+        {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&target)
+            .await
+            .with_context(|| format!("failed to create {}", target.display()))?;
+        fs::rename(&legacy, target.join("conversations"))
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to move {} into {}",
+                    legacy.display(),
+                    target.display()
+                )
+            })?;
+        info!(%account, "moved existing conversation store under the account directory");
+        Ok(())
+    }
+
+    /// The key records are sealed with, if the store is encrypted.
+    pub fn key(&self) -> Option<&Arc<StoreKey>> {
+        self.key.as_ref()
+    }
+
+    pub async fn append(&self, conversation: &ConversationId, event: &StoredEvent) -> Result<()> {
+        let mut line = self.encode_line(event)?;
+        line.push(b'\n');
+
+        let path = self.conversation_path(conversation);
+        let _guard = self.write_lock.lock().await;
+        if self.key.is_some() && !fs::try_exists(&path).await.unwrap_or(false) {
+            self.index_conversation(conversation).await?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        file.write_all(&line)
+            .await
+            .with_context(|| format!("failed to append to {}", path.display()))?;
+        self.track_expiry(conversation, std::slice::from_ref(event), false);
+
+        debug!(conversation = %conversation.0, "store event appended");
+        Ok(())
+    }
+
+    /// Stored events for `conversation`, leaving out disappearing messages past their timer.
+    pub async fn events(&self, conversation: &ConversationId) -> Result<Vec<StoredEvent>> {
+        let now = Utc::now();
+        let mut events = self.read_events(conversation).await?;
+        events.retain(|event| !event.expired_at(now));
+        Ok(events)
+    }
+
+    async fn read_events(&self, conversation: &ConversationId) -> Result<Vec<StoredEvent>> {
+        let path = self.conversation_path(conversation);
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                self.decode_line(line)
+                    .with_context(|| format!("corrupt store entry in {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Conversations that have a log in this store.
+    pub async fn conversations(&self) -> Result<Vec<ConversationId>> {
+        if self.key.is_some() {
+            let mut conversations = Vec::new();
+            for conversation in self.read_index(INDEX_FILE).await? {
+                if fs::try_exists(self.conversation_path(&conversation))
+                    .await
+                    .unwrap_or(false)
+                {
+                    conversations.push(conversation);
+                }
+            }
+            return Ok(conversations);
+        }
+
+        let mut conversations = Vec::new();
+        let mut entries = fs::read_dir(&self.conversations_dir)
+            .await
+            .with_context(|| format!("failed to list {}", self.conversations_dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_file_name)
+            {
+                conversations.push(ConversationId(id));
+            }
+        }
+        Ok(conversations)
+    }
+
+    /// Drop events older than `cutoff` and all but the newest `max_messages` messages.
+    pub async fn retain(
+        &self,
+        conversation: &ConversationId,
+        cutoff: Option<DateTime<Utc>>,
+        max_messages: Option<usize>,
+    ) -> Result<Pruned> {
+        self.prune_with(conversation, |events| {
+            let mut expired: Vec<bool> = events
+                .iter()
+                .map(|event| cutoff.is_some_and(|cutoff| event.occurred_at() < cutoff))
+                .collect();
+
+            // Imported history can be appended after newer records, so rank by timestamp.
+            let mut messages: Vec<(DateTime<Utc>, usize)> = events
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| matches!(event, StoredEvent::Message(_)))
+                .map(|(index, event)| (event.occurred_at(), index))
+                .collect();
+            messages.sort();
+            let excess = max_messages.map_or(0, |max| messages.len().saturating_sub(max));
+            for (_, index) in &messages[..excess] {
+                expired[*index] = true;
+            }
+            expired
+        })
+        .await
+    }
+
+    /// Delete disappearing messages whose timer elapsed by `now`, in every conversation.
+    ///
+    /// A conversation that cannot be rewritten is logged and skipped so the
+    /// others still expire on time. What was removed is returned either way,
+    /// alongside an error naming how many conversations failed.
+    pub async fn expire_due(&self, now: DateTime<Utc>) -> (Pruned, Result<()>) {
+        let due: Vec<ConversationId> = self
+            .expiries
+            .lock()
+            .expect("expiry index poisoned")
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(conversation, _)| conversation.clone())
+            .collect();
+
+        let mut pruned = Pruned::default();
+        let mut failed = 0;
+        for conversation in due {
+            match self
+                .prune_with(&conversation, |events| {
+                    events.iter().map(|event| event.expired_at(now)).collect()
+                })
+                .await
+            {
+                Ok(removed) => {
+                    pruned.events += removed.events;
+                    pruned.attachments.extend(removed.attachments);
+                }
+                Err(err) => {
+                    failed += 1;
+                    warn!(
+                        conversation = %conversation.0,
+                        error = %format!("{:#}", err),
+                        "failed to delete expired messages"
+                    );
+                }
+            }
+        }
+        let result = match failed {
+            0 => Ok(()),
+            failed => Err(anyhow!(
+                "expired messages could not be deleted from {} conversation(s)",
+                failed
+            )),
+        };
+        (pruned, result)
+    }
+
+    /// Rewrite a conversation log without the events `remove` flags.
+    ///
+    /// The kept records are written to a new file before the old one is
+    /// overwritten and unlinked, so removed records do not linger on disk.
+    async fn prune_with(
+        &self,
+        conversation: &ConversationId,
+        remove: impl FnOnce(&[StoredEvent]) -> Vec<bool>,
+    ) -> Result<Pruned> {
+        let path = self.conversation_path(conversation);
+        let _guard = self.write_lock.lock().await;
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                self.track_expiry(conversation, &[], true);
+                return Ok(Pruned::default());
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        let lines: Vec<&str> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let events = lines
+            .iter()
+            .map(|line| {
+                self.decode_line(line)
+                    .with_context(|| format!("corrupt store entry in {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let flags = remove(&events);
+        let mut pruned = Pruned::default();
+        let mut kept = Vec::with_capacity(contents.len());
+        let mut kept_events = Vec::with_capacity(events.len());
+        for ((line, event), removed) in lines.iter().zip(events).zip(flags) {
+            if removed {
+                pruned.events += 1;
+                pruned.attachments.extend(event.attachments());
+            } else {
+                kept.extend_from_slice(line.as_bytes());
+                kept.push(b'\n');
+                kept_events.push(event);
+            }
+        }
+        self.track_expiry(conversation, &kept_events, true);
+        if pruned.events == 0 {
+            return Ok(pruned);
+        }
+
+        if kept.is_empty() {
+            shred_file(&path).await?;
+            self.unindex_conversation(conversation).await?;
+        } else {
+            let temporary = path.with_extension("jsonl.tmp");
+            fs::write(&temporary, &kept)
+                .await
+                .with_context(|| format!("failed to write {}", temporary.display()))?;
+            shred_file(&path).await?;
+            fs::rename(&temporary, &path)
+                .await
+                .with_context(|| format!("failed to replace {}", path.display()))?;
+        }
+        debug!(conversation = %conversation.0, removed = pruned.events, "store events pruned");
+        Ok(pruned)
+    }
+
+    /// Note the earliest disappearing-message deadline among `events`.
+    ///
+    /// With `replace` the entry is recomputed from `events` alone; otherwise an
+    /// earlier deadline wins over the one already recorded.
+    fn track_expiry(&self, conversation: &ConversationId, events: &[StoredEvent], replace: bool) {
+        let earliest = events.iter().filter_map(StoredEvent::expires_at).min();
+        let mut expiries = self.expiries.lock().expect("expiry index poisoned");
+        match (earliest, expiries.get(conversation).copied()) {
+            (Some(earliest), Some(current)) if !replace && current <= earliest => {}
+            (Some(earliest), _) => {
+                expiries.insert(conversation.clone(), earliest);
+            }
+            (None, _) if replace => {
+                expiries.remove(conversation);
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Remove every stored event for `conversation`.
+    pub async fn purge(&self, conversation: &ConversationId) -> Result<Pruned> {
+        let path = self.conversation_path(conversation);
+        let _guard = self.write_lock.lock().await;
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Pruned::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        let mut pruned = Pruned::default();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            pruned.events += 1;
+            // An unreadable record still gets shredded; only its attachments are unknown.
+            match self.decode_line(line) {
+                Ok(event) => pruned.attachments.extend(event.attachments()),
+                Err(err) => warn!(?err, path = %path.display(), "skipping undecodable record"),
+            }
+        }
+        shred_file(&path).await?;
+        self.unindex_conversation(conversation).await?;
+        self.track_expiry(conversation, &[], true);
+        info!(conversation = %conversation.0, events = pruned.events, "conversation purged");
+        Ok(pruned)
+    }
+
+    fn encode_line(&self, event: &StoredEvent) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(event).context("failed to encode store event")?;
+        match &self.key {
+            Some(key) => key.seal_line(&json),
+            None => Ok(json),
+        }
+    }
+
+    fn decode_line(&self, line: &str) -> Result<StoredEvent> {
+        // Plaintext records are JSON objects; sealed ones are base64 and never start with `{`.
+        let plaintext = line.trim_start().starts_with('{');
+        match &self.key {
+            Some(_) if plaintext => Err(anyhow!(
+                "plaintext record in an encrypted store; it was not written by this server"
+            )),
+            Some(key) => {
+                serde_json::from_slice(&key.open_line(line)?).context("invalid JSON record")
+            }
+            None if plaintext => serde_json::from_str(line).context("invalid JSON record"),
+            None => Err(anyhow!("record is encrypted but no store key is loaded")),
+        }
+    }
+
+    /// Conversation IDs listed in the sealed index `name`, without duplicates.
+    async fn read_index(&self, name: &str) -> Result<Vec<ConversationId>> {
+        let Some(key) = &self.key else {
+            return Ok(Vec::new());
+        };
+        let path = self.conversations_dir.join(name);
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", path.display()))
+            }
+        };
+
+        let mut seen = HashSet::new();
+        let mut conversations = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let id: String = serde_json::from_slice(&key.open_line(line)?)
+                .with_context(|| format!("corrupt entry in {}", path.display()))?;
+            if seen.insert(id.clone()) {
+                conversations.push(ConversationId(id));
+            }
+        }
+        Ok(conversations)
+    }
+
+    /// Add `conversation` to the sealed index. Callers hold `write_lock`.
+    async fn index_conversation(&self, conversation: &ConversationId) -> Result<()> {
+        self.append_index(INDEX_FILE, conversation).await
+    }
+
+    async fn append_index(&self, name: &str, conversation: &ConversationId) -> Result<()> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let mut line = key.seal_line(
+            &serde_json::to_vec(&conversation.0).context("failed to encode index entry")?,
+        )?;
+        line.push(b'\n');
+        let path = self.conversations_dir.join(name);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        file.write_all(&line)
+            .await
+            .with_context(|| format!("failed to append to {}", path.display()))
+    }
+
+    /// Drop `conversation` from the sealed index. Callers hold `write_lock`.
+    async fn unindex_conversation(&self, conversation: &ConversationId) -> Result<()> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let remaining: Vec<ConversationId> = self
+            .read_index(INDEX_FILE)
+            .await?
+            .into_iter()
+            .filter(|entry| entry != conversation)
+            .collect();
+        let mut contents = Vec::new();
+        for entry in &remaining {
+            contents.extend(key.seal_line(
+                &serde_json::to_vec(&entry.0).context("failed to encode index entry")?,
+            )?);
+            contents.push(b'\n');
+        }
+        let path = self.conversations_dir.join(INDEX_FILE);
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, &contents)
+            .await
+            .with_context(|| format!("failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &path)
+            .await
+            .with_context(|| format!("failed to replace {}", path.display()))
+    }
+
+    /// Move files from the unencrypted layout to the encrypted one.
+    ///
+    /// Runs once per account, when a key is loaded but the store has no index
+    /// yet: plaintext records are sealed, hex-named files are renamed to their
+    /// keyed names and shredded, and the index is written last. An interrupted
+    /// pass resumes from `index.partial`. Afterwards plaintext records are
+    /// refused rather than sealed, so none can be slipped into the store.
+    async fn seal_legacy_files(&self) -> Result<()> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let _guard = self.write_lock.lock().await;
+        let indexed: HashSet<ConversationId> = self
+            .read_index(PARTIAL_INDEX_FILE)
+            .await?
+            .into_iter()
+            .collect();
+        // Files moved before an interruption; their keyed names may happen to decode as hex.
+        let moved: HashSet<PathBuf> = indexed
+            .iter()
+            .map(|conversation| self.conversation_path(conversation))
+            .collect();
+
+        let mut entries = fs::read_dir(&self.conversations_dir)
+            .await
+            .with_context(|| format!("failed to list {}", self.conversations_dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl")
+                || moved.contains(&path)
+            {
+                continue;
+            }
+            let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_file_name)
+            else {
+                continue;
+            };
+            let conversation = ConversationId(id);
+            let contents = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("failed to read {}", path.display()))?;
+
+            let mut sealed = Vec::with_capacity(contents.len() * 2);
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                if line.trim_start().starts_with('{') {
+                    sealed.extend(key.seal_line(line.as_bytes())?);
+                } else {
+                    sealed.extend_from_slice(line.as_bytes());
+                }
+                sealed.push(b'\n');
+            }
+            if !indexed.contains(&conversation) {
+                self.append_index(PARTIAL_INDEX_FILE, &conversation).await?;
+            }
+            let target = self.conversation_path(&conversation);
+            let temporary = target.with_extension("jsonl.tmp");
+            fs::write(&temporary, &sealed)
+                .await
+                .with_context(|| format!("failed to write {}", temporary.display()))?;
+            fs::rename(&temporary, &target)
+                .await
+                .with_context(|| format!("failed to replace {}", target.display()))?;
+            shred_file(&path).await?;
+            info!(path = %path.display(), "moved conversation log to the encrypted layout");
+        }
+
+        let partial = self.conversations_dir.join(PARTIAL_INDEX_FILE);
+        let index = self.conversations_dir.join(INDEX_FILE);
+        if !fs::try_exists(&partial).await.unwrap_or(false) {
+            fs::write(&partial, b"")
+                .await
+                .with_context(|| format!("failed to write {}", partial.display()))?;
+        }
+        fs::rename(&partial, &index)
+            .await
+            .with_context(|| format!("failed to replace {}", index.display()))
+    }
+
+    fn conversation_path(&self, conversation: &ConversationId) -> PathBuf {
+        let name = match &self.key {
+            Some(key) => key.file_name(&conversation.0),
+            None => conversation
+                .0
+                .bytes()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        };
+        self.conversations_dir.join(format!("{}.jsonl", name))
+    }
+}
+
+/// Overwrite `path` with zeros, flush it to disk and unlink it.
+///
+/// Returns `false` when the file did not exist.
+pub async fn shred_file(path: &Path) -> Result<bool> {
+    let mut file = match OpenOptions::new().write(true).open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).with_context(|| format!("failed to open {}", path.display())),
+    };
+    let length = file
+        .metadata()
+        .await
+        .with_context(|| format!("failed to stat {}", path.display()))?
+        .len();
+    let zeros = vec![0u8; 64 * 1024];
+    let mut remaining = length;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])
+            .await
+            .with_context(|| format!("failed to overwrite {}", path.display()))?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+        .await
+        .with_context(|| format!("failed to flush {}", path.display()))?;
+    drop(file);
+    fs::remove_file(path)
+        .await
+        .with_context(|| format!("failed to remove {}", path.display()))?;
+    Ok(true)
+}
+
+/// Inverse of [`Store::conversation_path`]'s hex encoding.
+fn decode_file_name(stem: &str) -> Option<String> {
+    let bytes = stem
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Take the exclusive lock on an account directory without waiting for it.
+///
+/// A server keeps the lock for as long as it runs, so CLI commands that
+/// rewrite the store cannot race its retention and expiry jobs.
+fn lock_account(root: &Path, account: &str) -> Result<std::fs::File> {
+    let path = root.join(LOCK_FILE);
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(std::fs::TryLockError::WouldBlock) => Err(anyhow!(
+            "the store for {} is in use by another signal-mcp-server process; stop the running server and try again",
+            account
+        )),
+        Err(std::fs::TryLockError::Error(err)) => {
+            Err(err).with_context(|| format!("failed to lock {}", path.display()))
+        }
+    }
+}
+
+fn account_root(storage: &Path, account: &str) -> PathBuf {
+    storage.join("accounts").join(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::mcp::{MessageBody, MessageId, Participant};
+
+    const ACCOUNT: &str = "+15551234567";
+
+    /// Temporary storage directory, removed again when dropped.
+    struct Scratch {
+        root: PathBuf,
+    }
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "signal-mcp-store-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("accounts").join(ACCOUNT).join("conversations"))
+                .unwrap();
+            Self { root }
+        }
+
+        fn conversations_dir(&self) -> PathBuf {
+            self.root
+                .join("accounts")
+                .join(ACCOUNT)
+                .join("conversations")
+        }
+
+        fn file_names(&self) -> Vec<String> {
+            let mut names: Vec<String> = std::fs::read_dir(self.conversations_dir())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+
+        async fn open(&self, key: Option<&Arc<StoreKey>>) -> Store {
+            Store::open_account(&self.root, ACCOUNT, key.cloned())
+                .await
+                .unwrap()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn message(conversation: &str, text: &str, seconds: i64) -> StoredEvent {
+        let timestamp = Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap();
+        StoredEvent::Message(Message {
+            id: MessageId(format!("{}:{}", conversation, timestamp.timestamp_millis())),
+            conversation_id: ConversationId(conversation.to_string()),
+            author: Participant {
+                address: conversation.to_string(),
+                display_name: None,
+            },
+            timestamp,
+            body: MessageBody::Text {
+                text: text.to_string(),
+                mentions: Vec::new(),
+                styles: Vec::new(),
+            },
+            attachments: Vec::new(),
+            expires_in_seconds: None,
+        })
+    }
+
+    fn texts(events: &[StoredEvent]) -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                StoredEvent::Message(Message {
+                    body: MessageBody::Text { text, .. },
+                    ..
+                }) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn hex(id: &str) -> String {
+        id.bytes().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn plaintext_log(events: &[StoredEvent]) -> String {
+        events
+            .iter()
+            .map(|event| serde_json::to_string(event).unwrap() + "\n")
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sealing_migrates_hex_named_plaintext_logs() {
+        let scratch = Scratch::new("migrate");
+        let conversation = ConversationId("+15550000001".to_string());
+        let legacy = scratch
+            .conversations_dir()
+            .join(format!("{}.jsonl", hex(&conversation.0)));
+        std::fs::write(
+            &legacy,
+            plaintext_log(&[
+                message(&conversation.0, "first", 0),
+                message(&conversation.0, "second", 1),
+            ]),
+        )
+        .unwrap();
+
+        let key = Arc::new(StoreKey::for_tests(b"secret"));
+        let store = scratch.open(Some(&key)).await;
+
+        assert!(!legacy.exists());
+        let sealed_name = format!("{}.jsonl", key.file_name(&conversation.0));
+        let mut expected = vec![sealed_name.clone(), INDEX_FILE.to_string()];
+        expected.sort();
+        assert_eq!(scratch.file_names(), expected);
+        let sealed =
+            std::fs::read_to_string(scratch.conversations_dir().join(&sealed_name)).unwrap();
+        assert!(!sealed.contains("first") && !sealed.contains('{'));
+        assert_eq!(
+            store.conversations().await.unwrap(),
+            std::slice::from_ref(&conversation)
+        );
+        assert_eq!(
+            texts(&store.events(&conversation).await.unwrap()),
+            ["first", "second"]
+        );
+
+        // Later starts find the index and leave the sealed layout alone.
+        drop(store);
+        let store = scratch.open(Some(&key)).await;
+        assert_eq!(
+            texts(&store.events(&conversation).await.unwrap()),
+            ["first", "second"]
+        );
+    }
+
+    #[tokio::test]
+    async fn sealing_resumes_from_the_partial_index() {
+        let scratch = Scratch::new("resume");
+        let key = Arc::new(StoreKey::for_tests(b"secret"));
+        let moved = ConversationId("+15550000001".to_string());
+        let pending = ConversationId("+15550000002".to_string());
+
+        // An earlier pass sealed and indexed `moved`, then stopped before `pending`.
+        let sealed_line = key
+            .seal_line(
+                serde_json::to_string(&message(&moved.0, "moved", 0))
+                    .unwrap()
+                    .as_bytes(),
+            )
+            .unwrap();
+        std::fs::write(
+            scratch
+                .conversations_dir()
+                .join(format!("{}.jsonl", key.file_name(&moved.0))),
+            [sealed_line, b"\n".to_vec()].concat(),
+        )
+        .unwrap();
+        let index_line = key
+            .seal_line(&serde_json::to_vec(&moved.0).unwrap())
+            .unwrap();
+        std::fs::write(
+            scratch.conversations_dir().join(PARTIAL_INDEX_FILE),
+            [index_line, b"\n".to_vec()].concat(),
+        )
+        .unwrap();
+        std::fs::write(
+            scratch
+                .conversations_dir()
+                .join(format!("{}.jsonl", hex(&pending.0))),
+            plaintext_log(&[message(&pending.0, "pending", 1)]),
+        )
+        .unwrap();
+
+        let store = scratch.open(Some(&key)).await;
+
+        assert!(!scratch
+            .conversations_dir()
+            .join(PARTIAL_INDEX_FILE)
+            .exists());
+        let mut conversations = store.conversations().await.unwrap();
+        conversations.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(conversations, [moved.clone(), pending.clone()]);
+        assert_eq!(texts(&store.events(&moved).await.unwrap()), ["moved"]);
+        assert_eq!(texts(&store.events(&pending).await.unwrap()), ["pending"]);
+    }
+
+    #[tokio::test]
+    async fn encrypted_store_refuses_plaintext_records() {
+        let scratch = Scratch::new("refuse");
+        let key = Arc::new(StoreKey::for_tests(b"secret"));
+        let store = scratch.open(Some(&key)).await;
+        let conversation = ConversationId("+15550000001".to_string());
+        store
+            .append(&conversation, &message(&conversation.0, "sealed", 0))
+            .await
+            .unwrap();
+
+        let path = store.conversation_path(&conversation);
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.push_str(&plaintext_log(&[message(&conversation.0, "injected", 1)]));
+        std::fs::write(&path, contents).unwrap();
+
+        let err = store.events(&conversation).await.unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("plaintext record in an encrypted store"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn retention_keeps_the_newest_messages_by_timestamp() {
+        let scratch = Scratch::new("retain-order");
+        let store = scratch.open(None).await;
+        let conversation = ConversationId("+15550000001".to_string());
+        // Imported history lands after messages received earlier but sent later.
+        for (text, seconds) in [("new-1", 100), ("new-2", 200), ("old-1", 10), ("old-2", 20)] {
+            store
+                .append(&conversation, &message(&conversation.0, text, seconds))
+                .await
+                .unwrap();
+        }
+        let change = StoredEvent::IdentityChange(IdentityChange {
+            address: conversation.0.clone(),
+            detected_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            action: IdentityAction::AutoTrusted,
+        });
+        store.append(&conversation, &change).await.unwrap();
+
+        // Only messages count towards the limit.
+        let pruned = store.retain(&conversation, None, Some(3)).await.unwrap();
+        assert_eq!(pruned.events, 1);
+        let events = store.events(&conversation).await.unwrap();
+        assert_eq!(texts(&events), ["new-1", "new-2", "old-2"]);
+        assert_eq!(events.len(), 4);
+
+        let cutoff = Utc.timestamp_opt(1_700_000_150, 0).unwrap();
+        let pruned = store
+            .retain(&conversation, Some(cutoff), Some(3))
+            .await
+            .unwrap();
+        assert_eq!(pruned.events, 3);
+        assert_eq!(
+            texts(&store.events(&conversation).await.unwrap()),
+            ["new-2"]
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use serde::Deserialize;
+
+use crate::mcp::{Mention, TextStyle, TextStyleKind};
+
+/// Outgoing mention span in the shape signal-cli's `--mention` flag expects.
+///
+/// Offsets are UTF-16 code units, matching how Signal clients index message text.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MentionSpan {
+    pub start: u32,
+    pub length: u32,
+    pub recipient: String,
+}
+
+impl MentionSpan {
+    pub fn to_cli_arg(&self) -> String {
+        format!("{}:{}:{}", self.start, self.length, self.recipient)
+    }
+}
+
+pub fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+/// Marker used for each style when rendering to and parsing from Markdown.
+fn style_marker(style: TextStyleKind) -> &'static str {
+    match style {
+        TextStyleKind::Bold => "**",
+        TextStyleKind::Italic => "_",
+        TextStyleKind::Spoiler => "||",
+        TextStyleKind::Strikethrough => "~~",
+        TextStyleKind::Monospace => "`",
+    }
+}
+
+/// Style name as accepted by signal-cli's `--text-style` flag and emitted in `textStyles`.
+pub fn style_cli_name(style: TextStyleKind) -> &'static str {
+    match style {
+        TextStyleKind::Bold => "BOLD",
+        TextStyleKind::Italic => "ITALIC",
+        TextStyleKind::Spoiler => "SPOILER",
+        TextStyleKind::Strikethrough => "STRIKETHROUGH",
+        TextStyleKind::Monospace => "MONOSPACE",
+    }
+}
+
+pub fn parse_style_name(name: &str) -> Option<TextStyleKind> {
+    match name.to_ascii_uppercase().as_str() {
+        "BOLD" => Some(TextStyleKind::Bold),
+        "ITALIC" => Some(TextStyleKind::Italic),
+        "SPOILER" => Some(TextStyleKind::Spoiler),
+        "STRIKETHROUGH" => Some(TextStyleKind::Strikethrough),
+        "MONOSPACE" => Some(TextStyleKind::Monospace),
+        _ => None,
+    }
+}
+
+pub fn style_cli_arg(style: &TextStyle) -> String {
+    format!(
+        "{}:{}:{}",
+        style.start,
+        style.length,
+        style_cli_name(style.style)
+    )
+}
+
+/// Render a text body as Markdown, replacing mention ranges with `@Name`.
+///
+/// Styles that overlap without nesting produce interleaved markers; Signal
+/// clients rarely emit those, so no attempt is made to split them.
+pub fn render_markdown(text: &str, mentions: &[Mention], styles: &[TextStyle]) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut open: Vec<&TextStyle> = Vec::new();
+    let mut position = 0u32;
+    let mut skip_until = 0u32;
+
+    for ch in text.chars() {
+        close_styles(&mut rendered, &mut open, position);
+        let mut opening: Vec<&TextStyle> = styles
+            .iter()
+            .filter(|style| style.start == position && style.length > 0)
+            .collect();
+        opening.sort_by_key(|style| std::cmp::Reverse(style_end(style)));
+        for style in opening {
+            rendered.push_str(style_marker(style.style));
+            open.push(style);
+        }
+
+        if position >= skip_until {
+            if let Some(mention) = mentions
+                .iter()
+                .find(|mention| mention.start == position && mention.length > 0)
+            {
+                rendered.push('@');
+                rendered.push_str(
+                    mention
+                        .participant
+                        .display_name
+                        .as_deref()
+                        .unwrap_or(&mention.participant.address),
+                );
+                skip_until = position.saturating_add(mention.length);
+            } else {
+                rendered.push(ch);
+            }
+        }
+        position += ch.len_utf16() as u32;
+    }
+
+    close_styles(&mut rendered, &mut open, u32::MAX);
+    rendered
+}
+
+fn style_end(style: &TextStyle) -> u32 {
+    style.start.saturating_add(style.length)
+}
+
+fn close_styles(rendered: &mut String, open: &mut Vec<&TextStyle>, position: u32) {
+    while let Some(index) = open.iter().rposition(|style| style_end(style) <= position) {
+        // Close everything opened after the finished style first to keep markers nested.
+        let closing: Vec<&TextStyle> = open.drain(index..).collect();
+        for style in closing.iter().rev() {
+            rendered.push_str(style_marker(style.style));
+        }
+        for style in closing.into_iter().skip(1) {
+            if style_end(style) > position {
+                rendered.push_str(style_marker(style.style));
+                open.push(style);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Marker {
+        style: TextStyleKind,
+        raw: &'static str,
+        can_open: bool,
+        can_close: bool,
+    },
+}
+
+/// Convert a Markdown subset into plain text plus Signal style ranges.
+///
+/// Supports `**bold**`, `*italic*`/`_italic_`, `~~strikethrough~~`,
+/// `||spoiler||` and `` `monospace` ``. Unpaired markers and backslash-escaped
+/// characters are kept literally.
+pub fn parse_markdown(input: &str) -> (String, Vec<TextStyle>) {
+    let tokens = tokenize_markdown(input);
+
+    // Pair each closing marker with the nearest unmatched opener of the same style.
+    let mut partner: Vec<Option<usize>> = vec![None; tokens.len()];
+    let mut openers: Vec<usize> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let Token::Marker {
+            raw,
+            can_open,
+            can_close,
+            ..
+        } = *token
+        else {
+            continue;
+        };
+        let matching = openers
+            .iter()
+            .rposition(|&open| matches!(tokens[open], Token::Marker { raw: open_raw, .. } if open_raw == raw))
+            .filter(|_| can_close);
+        match matching {
+            Some(position) => {
+                let open = openers.remove(position);
+                partner[open] = Some(index);
+                partner[index] = Some(open);
+            }
+            None if can_open => openers.push(index),
+            None => {}
+        }
+    }
+
+    let mut text = String::with_capacity(input.len());
+    let mut styles = Vec::new();
+    let mut starts: Vec<u32> = vec![0; tokens.len()];
+    let mut position = 0u32;
+    for (index, token) in tokens.iter().enumerate() {
+        match (token, partner[index]) {
+            (Token::Text(segment), _) => {
+                text.push_str(segment);
+                position += utf16_len(segment);
+            }
+            (Token::Marker { style, .. }, Some(open)) if open < index => {
+                if position > starts[open] {
+                    styles.push(TextStyle {
+                        start: starts[open],
+                        length: position - starts[open],
+                        style: *style,
+                    });
+                }
+            }
+            (Token::Marker { .. }, Some(_)) => starts[index] = position,
+            (Token::Marker { raw, .. }, None) => {
+                text.push_str(raw);
+                position += utf16_len(raw);
+            }
+        }
+    }
+
+    styles.sort_by_key(|style| style.start);
+    (text, styles)
+}
+
+fn tokenize_markdown(input: &str) -> Vec<Token> {
+    const MARKERS: [(&str, TextStyleKind); 5] = [
+        ("**", TextStyleKind::Bold),
+        ("~~", TextStyleKind::Strikethrough),
+        ("||", TextStyleKind::Spoiler),
+        ("*", TextStyleKind::Italic),
+        ("_", TextStyleKind::Italic),
+    ];
+
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut index = 0usize;
+
+    while index < input.len() {
+        let rest = &input[index..];
+
+        if let Some(escaped) = rest.strip_prefix('\\') {
+            if let Some(next) = escaped.chars().next().filter(|c| c.is_ascii_punctuation()) {
+                literal.push(next);
+                index += 1 + next.len_utf8();
+                continue;
+            }
+        }
+
+        if let Some(code) = rest.strip_prefix('`') {
+            if let Some(end) = code.find('`').filter(|&end| end > 0) {
+                flush_literal(&mut tokens, &mut literal);
+                tokens.push(code_marker(true));
+                tokens.push(Token::Text(code[..end].to_string()));
+                tokens.push(code_marker(false));
+                index += end + 2;
+                continue;
+            }
+        }
+
+        if let Some(&(raw, style)) = MARKERS.iter().find(|(raw, _)| rest.starts_with(raw)) {
+            let before = input[..index].chars().next_back();
+            let after = rest[raw.len()..].chars().next();
+            // Keep snake_case identifiers intact: `_` only counts at a word boundary.
+            let intraword = raw == "_"
+                && before.is_some_and(char::is_alphanumeric)
+                && after.is_some_and(char::is_alphanumeric);
+            if !intraword {
+                flush_literal(&mut tokens, &mut literal);
+                tokens.push(Token::Marker {
+                    style,
+                    raw,
+                    can_open: after.is_some_and(|c| !c.is_whitespace()),
+                    can_close: before.is_some_and(|c| !c.is_whitespace()),
+                });
+                index += raw.len();
+                continue;
+            }
+        }
+
+        let ch = rest.chars().next().expect("index is within input");
+        literal.push(ch);
+        index += ch.len_utf8();
+    }
+
+    flush_literal(&mut tokens, &mut literal);
+    tokens
+}
+
+fn code_marker(opening: bool) -> Token {
+    Token::Marker {
+        style: TextStyleKind::Monospace,
+        raw: "`",
+        can_open: opening,
+        can_close: !opening,
+    }
+}
+
+fn flush_literal(tokens: &mut Vec<Token>, literal: &mut String) {
+    if !literal.is_empty() {
+        tokens.push(Token::Text(std::mem::take(literal)));
+    }
+}
+
+/// Find `@Name` tokens that match a known contact name and turn them into mention spans.
+///
+/// `contacts` pairs an address with its display name. Matching is ASCII
+/// case-insensitive and prefers the longest name so "@Ann Lee" wins over "@Ann".
+pub fn find_named_mentions(text: &str, contacts: &[(String, String)]) -> Vec<MentionSpan> {
+    let mut spans = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut skip_until = 0usize;
+
+    for (idx, ch) in text.char_indices() {
+        let at_boundary = previous.is_none_or(|prev| !prev.is_alphanumeric());
+        previous = Some(ch);
+        if idx < skip_until || ch != '@' || !at_boundary {
+            continue;
+        }
+
+        let rest = &text[idx + 1..];
+        let best = contacts
+            .iter()
+            .filter(|(_, name)| !name.is_empty())
+            .filter(|(_, name)| {
+                rest.get(..name.len())
+                    .is_some_and(|candidate| candidate.eq_ignore_ascii_case(name))
+                    && rest[name.len()..]
+                        .chars()
+                        .next()
+                        .is_none_or(|next| !next.is_alphanumeric())
+            })
+            .max_by_key(|(_, name)| name.len());
+
+        if let Some((address, name)) = best {
+            let token_end = idx + 1 + name.len();
+            spans.push(MentionSpan {
+                start: utf16_len(&text[..idx]),
+                length: utf16_len(&text[idx..token_end]),
+                recipient: address.clone(),
+            });
+            skip_until = token_end;
+        }
+    }
+
+    spans
+}
+
+/// Add `named` mentions to the `explicit` ones, sorted by offset.
+///
+/// Explicit spans win: a named mention overlapping one of them is dropped.
+pub fn merge_mentions(explicit: &[MentionSpan], named: Vec<MentionSpan>) -> Vec<MentionSpan> {
+    let end = |span: &MentionSpan| span.start.saturating_add(span.length);
+    let mut mentions = explicit.to_vec();
+    for span in named {
+        let overlaps = explicit
+            .iter()
+            .any(|m| span.start < end(m) && m.start < end(&span));
+        if !overlaps {
+            mentions.push(span);
+        }
+    }
+    mentions.sort_by_key(|span| span.start);
+    mentions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use TextStyleKind::{Bold, Italic, Monospace, Spoiler, Strikethrough};
+
+    fn spans(styles: &[TextStyle]) -> Vec<(u32, u32, TextStyleKind)> {
+        styles
+            .iter()
+            .map(|style| (style.start, style.length, style.style))
+            .collect()
+    }
+
+    fn parsed(input: &str) -> (String, Vec<(u32, u32, TextStyleKind)>) {
+        let (text, styles) = parse_markdown(input);
+        (text, spans(&styles))
+    }
+
+    fn style(start: u32, length: u32, style: TextStyleKind) -> TextStyle {
+        TextStyle {
+            start,
+            length,
+            style,
+        }
+    }
+
+    #[test]
+    fn nested_styles_become_nested_ranges() {
+        assert_eq!(
+            parsed("**bold _both_ bold**"),
+            (
+                "bold both bold".to_string(),
+                vec![(0, 14, Bold), (5, 4, Italic)]
+            )
+        );
+        assert_eq!(
+            parsed("~~||gone||~~"),
+            (
+                "gone".to_string(),
+                vec![(0, 4, Spoiler), (0, 4, Strikethrough)]
+            )
+        );
+    }
+
+    #[test]
+    fn overlapping_styles_pair_by_marker() {
+        assert_eq!(
+            parsed("**a _b** c_"),
+            ("a b c".to_string(), vec![(0, 3, Bold), (2, 3, Italic)])
+        );
+    }
+
+    #[test]
+    fn overlapping_styles_render_as_nested_markers() {
+        let styles = [style(0, 3, Bold), style(2, 3, Italic)];
+        assert_eq!(render_markdown("a b c", &[], &styles), "**a _b_**_ c_");
+    }
+
+    #[test]
+    fn underscores_inside_words_stay_literal() {
+        assert_eq!(
+            parsed("snake_case_name and _it_"),
+            ("snake_case_name and it".to_string(), vec![(20, 2, Italic)])
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_keep_markers_literal() {
+        assert_eq!(
+            parsed(r"\*not italic\* and \_x\_ or \`y\`"),
+            ("*not italic* and _x_ or `y`".to_string(), vec![])
+        );
+        // Only punctuation can be escaped.
+        assert_eq!(parsed(r"C:\dir"), (r"C:\dir".to_string(), vec![]));
+    }
+
+    #[test]
+    fn unpaired_markers_stay_literal() {
+        for input in ["2 * 3 = 6", "**open", "close~~", "a ` b", "||a", "``"] {
+            assert_eq!(parsed(input), (input.to_string(), vec![]), "{input}");
+        }
+    }
+
+    #[test]
+    fn code_spans_suppress_inner_markers() {
+        assert_eq!(
+            parsed("`**not bold** _x_` and **bold**"),
+            (
+                "**not bold** _x_ and bold".to_string(),
+                vec![(0, 16, Monospace), (21, 4, Bold)]
+            )
+        );
+    }
+
+    #[test]
+    fn offsets_count_utf16_code_units() {
+        let (text, styles) = parse_markdown("😀 **hi** 👍 _x_ é");
+        assert_eq!(text, "😀 hi 👍 x é");
+        assert_eq!(spans(&styles), [(3, 2, Bold), (9, 1, Italic)]);
+        assert_eq!(render_markdown(&text, &[], &styles), "😀 **hi** 👍 _x_ é");
+    }
+
+    #[test]
+    fn parsed_markdown_renders_back_unchanged() {
+        for input in [
+            "**Bold** with _italic_, ~~strike~~, ||spoiler|| and `code`",
+            "**bold _both_**",
+            "**a _b_** c",
+            "plain text",
+        ] {
+            let (text, styles) = parse_markdown(input);
+            assert_eq!(render_markdown(&text, &[], &styles), input);
+        }
+    }
+
+    fn contacts() -> Vec<(String, String)> {
+        [
+            ("+15550000001", "Ann"),
+            ("+15550000002", "Ann Lee"),
+            ("+15550000003", "Zoë"),
+        ]
+        .into_iter()
+        .map(|(address, name)| (address.to_string(), name.to_string()))
+        .collect()
+    }
+
+    fn span(start: u32, length: u32, recipient: &str) -> MentionSpan {
+        MentionSpan {
+            start,
+            length,
+            recipient: recipient.to_string(),
+        }
+    }
+
+    #[test]
+    fn named_mentions_prefer_the_longest_name() {
+        assert_eq!(
+            find_named_mentions("@ann lee and @Ann, not @Annie", &contacts()),
+            [span(0, 8, "+15550000002"), span(13, 4, "+15550000001")]
+        );
+    }
+
+    #[test]
+    fn named_mentions_need_a_word_boundary() {
+        assert!(find_named_mentions("mail@Ann.example", &contacts()).is_empty());
+        assert!(find_named_mentions("@Anna", &contacts()).is_empty());
+    }
+
+    #[test]
+    fn named_mention_offsets_count_utf16_code_units() {
+        assert_eq!(
+            find_named_mentions("👋 @Ann Lee 🎉@Zoë!", &contacts()),
+            [span(3, 8, "+15550000002"), span(14, 4, "+15550000003")]
+        );
+    }
+
+    #[test]
+    fn explicit_mentions_win_over_overlapping_named_ones() {
+        let text = "@Ann Lee and @Zoë";
+        let explicit = [span(0, 4, "+15559999999"), span(13, 4, "+15550000003")];
+        let named = find_named_mentions(text, &contacts());
+        assert_eq!(
+            merge_mentions(&explicit, named),
+            [span(0, 4, "+15559999999"), span(13, 4, "+15550000003")]
+        );
+
+        let explicit = [span(9, 3, "+15559999999")];
+        assert_eq!(
+            merge_mentions(&explicit, find_named_mentions(text, &contacts())),
+            [
+                span(0, 8, "+15550000002"),
+                span(9, 3, "+15559999999"),
+                span(13, 4, "+15550000003")
+            ]
+        );
+    }
+
+    #[test]
+    fn merging_tolerates_spans_at_the_end_of_the_offset_range() {
+        let explicit = [span(u32::MAX - 1, 5, "+15559999999")];
+        assert_eq!(
+            merge_mentions(&explicit, vec![span(0, 4, "+15550000001")]).len(),
+            2
+        );
+    }
+}
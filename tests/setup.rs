@@ -0,0 +1,232 @@
+//! `link`, `register` and `verify` against a fake signal-cli script.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Records its arguments and answers like signal-cli would.
+const FAKE_SIGNAL_CLI: &str = r#"#!/bin/sh
+echo "$*" >> "$(dirname "$0")/calls.log"
+case "$*" in
+  link*)
+    echo "sgnl://linkdevice?uuid=test&pub_key=test"
+    echo "Associated with: +15557654321"
+    ;;
+  *" register"*)
+    case "$*" in
+      *--captcha*) ;;
+      *) if [ -e "$(dirname "$0")/captcha" ]; then
+           echo "Captcha required for verification" >&2
+           exit 1
+         fi ;;
+    esac
+    ;;
+  *" verify 123456"*) ;;
+  *" verify "*)
+    echo "Verification failed: incorrect code" >&2
+    exit 1
+    ;;
+  *)
+    echo "unexpected arguments: $*" >&2
+    exit 2
+    ;;
+esac
+"#;
+
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("signal-mcp-setup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let script = root.join("signal-cli");
+        fs::write(&script, FAKE_SIGNAL_CLI).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(root.join("config.toml"), "[retention]\n").unwrap();
+        Self { root }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_with_signal_cli(&self.root.join("signal-cli"), args)
+    }
+
+    fn run_with_signal_cli(&self, signal_cli: &Path, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_signal-mcp-server"))
+            .arg("--config")
+            .arg(self.root.join("config.toml"))
+            .arg("--signal-cli")
+            .arg(signal_cli)
+            .arg("--storage")
+            .arg(self.root.join("var"))
+            .args(args)
+            .env_remove("RUST_LOG")
+            .output()
+            .unwrap()
+    }
+
+    fn calls(&self) -> String {
+        fs::read_to_string(self.root.join("calls.log")).unwrap_or_default()
+    }
+
+    fn config(&self) -> String {
+        fs::read_to_string(self.root.join("config.toml")).unwrap()
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn touch(path: &Path) {
+    fs::write(path, "").unwrap();
+}
+
+#[test]
+fn link_shows_the_uri_and_records_the_account() {
+    let sandbox = Sandbox::new("link");
+    let output = sandbox.run(&["link", "--name", "laptop"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sandbox.calls().trim(), "link -n laptop");
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Linking URI: sgnl://linkdevice?uuid=test&pub_key=test"));
+    assert!(stdout.contains("Linked to +15557654321"));
+    let config = sandbox.config();
+    assert!(
+        config.starts_with("account = \"+15557654321\"\n"),
+        "{}",
+        config
+    );
+    assert!(config.contains("[retention]"));
+}
+
+#[test]
+fn register_passes_voice_and_captcha() {
+    let sandbox = Sandbox::new("register");
+    let output = sandbox.run(&["register", "+15551234567", "--voice"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Verification code requested by voice call"));
+
+    touch(&sandbox.path("captcha"));
+    let output = sandbox.run(&["register", "+15551234567"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("signalcaptchas.org"));
+
+    let output = sandbox.run(&[
+        "register",
+        "+15551234567",
+        "--captcha",
+        "signalcaptcha://token",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert_eq!(
+        sandbox.calls().lines().collect::<Vec<_>>(),
+        [
+            "--account +15551234567 register --voice",
+            "--account +15551234567 register",
+            "--account +15551234567 register --captcha signalcaptcha://token",
+        ]
+    );
+    assert!(!sandbox.config().contains("account ="));
+}
+
+#[test]
+fn verify_records_the_account_only_on_success() {
+    let sandbox = Sandbox::new("verify");
+    let output = sandbox.run(&["verify", "+15551234567", "000000"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("incorrect code"));
+    assert!(!sandbox.config().contains("account ="));
+
+    let output = sandbox.run(&["verify", "+15551234567", "123456", "--pin", "4321"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Verified +15551234567"));
+    assert!(sandbox.config().starts_with("account = \"+15551234567\"\n"));
+    assert_eq!(
+        sandbox.calls().lines().last(),
+        Some("--account +15551234567 verify 123456 --pin 4321")
+    );
+}
+
+#[test]
+fn recording_the_account_keeps_table_keys_and_replaces_the_top_level_one() {
+    let sandbox = Sandbox::new("config");
+    fs::write(
+        sandbox.path("config.toml"),
+        "# chosen at setup\nstorage = \"./var\"\n\n[retention]\naccount = \"unrelated\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(
+        sandbox.path("config.toml"),
+        fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+
+    let output = sandbox.run(&["link"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sandbox.config(),
+        "account = \"+15557654321\"\n# chosen at setup\nstorage = \"./var\"\n\n[retention]\naccount = \"unrelated\"\n"
+    );
+
+    let output = sandbox.run(&["verify", "+15551234567", "123456"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        sandbox.config(),
+        "account = \"+15551234567\"\n# chosen at setup\nstorage = \"./var\"\n\n[retention]\naccount = \"unrelated\"\n"
+    );
+    let mode = fs::metadata(sandbox.path("config.toml"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let leftovers: Vec<_> = fs::read_dir(&sandbox.root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+}
+
+#[test]
+fn store_commands_run_without_signal_cli_and_validation_creates_nothing() {
+    let sandbox = Sandbox::new("offline");
+    let missing = sandbox.path("missing-signal-cli");
+
+    let output = sandbox.run_with_signal_cli(&missing, &["--account", "+15551234567"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("signal_cli_path"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox.path("var").exists());
+
+    let output = sandbox.run_with_signal_cli(
+        &missing,
+        &["--account", "+15551234567", "purge", "+15550000001"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Purged 0 stored event(s)"));
+    assert!(sandbox.calls().is_empty());
+}